@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/anneal-solve ./weddings/ring-suite
+./target/release/score ./target/release/anneal-solve ./weddings/rand-suite
+./target/release/score ./target/release/anneal-solve ./weddings/complete-suite
+./target/release/score ./target/release/anneal-solve ./weddings/tense-suite
@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/bnb-solve ./weddings/ring-suite -- --time-limit 60
+./target/release/score ./target/release/bnb-solve ./weddings/rand-suite -- --time-limit 60
+./target/release/score ./target/release/bnb-solve ./weddings/complete-suite -- --time-limit 60
+./target/release/score ./target/release/bnb-solve ./weddings/tense-suite -- --time-limit 60
@@ -0,0 +1,4 @@
+#!/bin/sh
+./target/release/score ./target/release/genetic-solve ./weddings/rand-big
+./target/release/score ./target/release/genetic-solve ./weddings/rand-big -- --local-search stochastic
+./target/release/score ./target/release/lahc-solve ./weddings/rand-big
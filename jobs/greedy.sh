@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/greedy-solve ./weddings/ring-suite
+./target/release/score ./target/release/greedy-solve ./weddings/rand-suite
+./target/release/score ./target/release/greedy-solve ./weddings/complete-suite
+./target/release/score ./target/release/greedy-solve ./weddings/tense-suite
@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/kl-solve ./weddings/ring-suite
+./target/release/score ./target/release/kl-solve ./weddings/rand-suite
+./target/release/score ./target/release/kl-solve ./weddings/complete-suite
+./target/release/score ./target/release/kl-solve ./weddings/tense-suite
@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/lns-solve ./weddings/rand-suite
+./target/release/score ./target/release/lns-solve ./weddings/rand-suite -- --repair exact
+./target/release/score ./target/release/lns-solve ./weddings/rand-big
+./target/release/score ./target/release/lns-solve ./weddings/rand-big -- --repair exact
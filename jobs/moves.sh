@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/lahc-solve ./weddings/rand-suite -- --moves swap
+./target/release/score ./target/release/lahc-solve ./weddings/rand-suite -- --moves swap=2,cycle=1,chain=1
+./target/release/score ./target/release/anneal-solve ./weddings/rand-suite -- --moves swap
+./target/release/score ./target/release/anneal-solve ./weddings/rand-suite -- --moves swap=2,cycle=1,chain=1
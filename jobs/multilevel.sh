@@ -0,0 +1,3 @@
+#!/bin/sh
+./target/release/score ./target/release/multilevel-solve ./weddings/rand-big
+./target/release/score ./target/release/hill-solve ./weddings/rand-big
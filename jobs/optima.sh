@@ -0,0 +1,2 @@
+#!/bin/sh
+./target/release/known-optima ./weddings/complete-suite ./weddings/ring-suite ./weddings/rand-suite
@@ -0,0 +1,7 @@
+#!/bin/sh
+./target/release/score ./target/release/hill-solve ./weddings/rand-suite -- --sampling uniform
+./target/release/score ./target/release/hill-solve ./weddings/rand-suite -- --sampling unhappiest
+./target/release/score ./target/release/hill-solve ./weddings/rand-suite -- --sampling most-to-gain
+./target/release/score ./target/release/anneal-solve ./weddings/rand-suite -- --sampling uniform
+./target/release/score ./target/release/anneal-solve ./weddings/rand-suite -- --sampling unhappiest
+./target/release/score ./target/release/anneal-solve ./weddings/rand-suite -- --sampling most-to-gain
@@ -0,0 +1,5 @@
+#!/bin/sh
+./target/release/score ./target/release/tabu-solve ./weddings/ring-suite
+./target/release/score ./target/release/tabu-solve ./weddings/rand-suite
+./target/release/score ./target/release/tabu-solve ./weddings/complete-suite
+./target/release/score ./target/release/tabu-solve ./weddings/tense-suite
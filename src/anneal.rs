@@ -0,0 +1,329 @@
+use crate::hill_climb::{
+    check_feasible, check_groups, fitness, make_move, random_plan, undo_move, MoveScorer,
+    Neighbourhood,
+};
+use crate::metrics::Metrics;
+use crate::objective::{NumericScore, Objective, TotalHappiness};
+use crate::{GuestSampling, LocalSearch, MoveMix, Plan, PlanError, Problem, SeatingPlanner};
+
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use rand::prelude::*;
+
+// How many moves we try per guest, unless told otherwise.
+const DEFAULT_ITERATIONS_PER_GUEST: usize = 1000;
+
+// How many random moves we look at to estimate the starting temperature.
+const TEMPERATURE_SAMPLES: usize = 100;
+
+// How likely an average worsening move is to be accepted at the
+// start and end of cooling, when we pick the temperatures ourselves.
+const INITIAL_ACCEPTANCE: f64 = 0.8;
+const FINAL_ACCEPTANCE: f64 = 0.001;
+
+// Each reheat starts from this fraction of the previous starting temperature.
+const REHEAT_FACTOR: f64 = 0.5;
+
+/// How the temperature falls from its starting to its final value.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum CoolingSchedule {
+    /// Multiplies the temperature by the same factor every iteration.
+    Geometric,
+    /// Takes the same amount off the temperature every iteration.
+    Linear,
+    /// Lundy and Mees' schedule, `T' = T / (1 + beta * T)`, which cools
+    /// quickly while hot and slowly once cold.
+    LundyMees,
+}
+
+impl CoolingSchedule {
+    /// Returns the temperature after `step` of `n_steps` iterations, going
+    /// from `initial` at the first step to `last` at the final one.
+    pub fn temperature(self, initial: f64, last: f64, step: usize, n_steps: usize) -> f64 {
+        let progress = step as f64 / n_steps as f64;
+        match self {
+            CoolingSchedule::Geometric => initial * (last / initial).powf(progress),
+            CoolingSchedule::Linear => initial + (last - initial) * progress,
+            CoolingSchedule::LundyMees => {
+                // `1 / T` goes up by `beta` every iteration.
+                let beta = (initial - last) / (n_steps as f64 * initial * last);
+                initial / (1.0 + beta * step as f64 * initial)
+            }
+        }
+    }
+}
+
+impl FromStr for CoolingSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "geometric" | "exponential" => Ok(CoolingSchedule::Geometric),
+            "linear" => Ok(CoolingSchedule::Linear),
+            "lundy-mees" | "lundymees" | "adaptive" => Ok(CoolingSchedule::LundyMees),
+            _ => Err(anyhow!("Unrecognised cooling schedule")),
+        }
+    }
+}
+
+/// Makes random moves, always keeping improvements and keeping worse plans
+/// with a probability that shrinks as the temperature cools.
+/// Moves that break more hard constraints are never kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedAnnealingPlanner<R, O = TotalHappiness> {
+    rng: R,
+    objective: O,
+    schedule: CoolingSchedule,
+    // Iterations per cooling run. If missing, this depends on the number of guests.
+    n_iterations: Option<NonZeroUsize>,
+    // Starting and final temperatures. If missing, these are estimated
+    // from the problem.
+    temperatures: Option<(f64, f64)>,
+    // How many more times to cool down from the best plan after the first run.
+    n_reheats: usize,
+    moves: MoveMix,
+    sampling: GuestSampling,
+}
+
+impl<R> SimulatedAnnealingPlanner<R>
+where
+    R: Rng,
+{
+    pub fn new(rng: R) -> Self {
+        Self::with_objective(rng, TotalHappiness)
+    }
+}
+
+impl<R, O> SimulatedAnnealingPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+    O::Score: NumericScore,
+{
+    pub fn with_objective(rng: R, objective: O) -> Self {
+        Self {
+            rng,
+            objective,
+            schedule: CoolingSchedule::Geometric,
+            n_iterations: None,
+            temperatures: None,
+            n_reheats: 0,
+            moves: MoveMix::default(),
+            sampling: GuestSampling::default(),
+        }
+    }
+
+    pub fn with_schedule(self, schedule: CoolingSchedule) -> Self {
+        Self { schedule, ..self }
+    }
+
+    /// Sets how many moves are tried each time the plan is cooled.
+    pub fn with_iterations(self, n_iterations: NonZeroUsize) -> Self {
+        Self {
+            n_iterations: Some(n_iterations),
+            ..self
+        }
+    }
+
+    /// Fixes the starting and final temperatures, rather than estimating
+    /// them from the problem.
+    /// # Panics
+    /// Panics unless `initial >= last > 0`.
+    pub fn with_temperatures(self, initial: f64, last: f64) -> Self {
+        assert!(initial >= last && last > 0.0);
+        Self {
+            temperatures: Some((initial, last)),
+            ..self
+        }
+    }
+
+    /// After cooling, heats the best plan found back up and cools it again,
+    /// this many times. Each reheat is half as hot as the last.
+    pub fn with_reheats(self, n_reheats: usize) -> Self {
+        Self { n_reheats, ..self }
+    }
+
+    /// Chooses which kinds of random move are tried.
+    pub fn with_moves(self, moves: MoveMix) -> Self {
+        Self { moves, ..self }
+    }
+
+    /// Chooses how the guests to move are picked.
+    pub fn with_sampling(self, sampling: GuestSampling) -> Self {
+        Self { sampling, ..self }
+    }
+
+    // Picks temperatures so that an average worsening move is usually
+    // accepted at first, and almost never by the end.
+    fn estimate_temperatures(
+        &mut self,
+        problem: &Problem,
+        neighbourhood: &Neighbourhood,
+        plan: &mut Plan,
+    ) -> (f64, f64) {
+        let current = fitness(&self.objective, &Metrics::new(plan, problem));
+
+        let mut worsenings = Vec::with_capacity(TEMPERATURE_SAMPLES);
+        for _ in 0..TEMPERATURE_SAMPLES {
+            if let Some(change) = neighbourhood.random_move(&mut self.rng, plan) {
+                make_move(plan, &change);
+                let new = fitness(&self.objective, &Metrics::new(plan, problem));
+                undo_move(plan, &change);
+
+                if new.0 == current.0 && new.1 < current.1 {
+                    worsenings.push(current.1.to_f64() - new.1.to_f64());
+                }
+            }
+        }
+
+        let mean_worsening = if worsenings.is_empty() {
+            1.0
+        } else {
+            worsenings.iter().sum::<f64>() / worsenings.len() as f64
+        };
+        (
+            -mean_worsening / INITIAL_ACCEPTANCE.ln(),
+            -mean_worsening / FINAL_ACCEPTANCE.ln(),
+        )
+    }
+}
+
+impl<R, O> SeatingPlanner for SimulatedAnnealingPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+    O::Score: NumericScore,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
+}
+
+impl<R, O> LocalSearch for SimulatedAnnealingPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+    O::Score: NumericScore,
+{
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        let neighbourhood = Neighbourhood::new(problem)
+            .with_moves(self.moves)
+            .with_sampling(self.sampling);
+
+        let n_iterations = self.n_iterations.map_or_else(
+            || DEFAULT_ITERATIONS_PER_GUEST * problem.relations().len(),
+            NonZeroUsize::get,
+        );
+        let (mut initial, last) = match self.temperatures {
+            Some(temperatures) => temperatures,
+            None => self.estimate_temperatures(problem, &neighbourhood, &mut plan),
+        };
+
+        let mut scorer = MoveScorer::new(&neighbourhood, &self.objective, &plan);
+        let mut current_fitness = scorer.fitness(&plan);
+        let mut best = plan.clone();
+        let mut best_fitness = current_fitness;
+
+        for run in 0..=self.n_reheats {
+            if run > 0 {
+                // Reheat, starting from the best plan so far.
+                initial = (initial * REHEAT_FACTOR).max(last);
+                plan.clone_from(&best);
+                scorer = MoveScorer::new(&neighbourhood, &self.objective, &plan);
+                current_fitness = best_fitness;
+            }
+
+            for step in 0..n_iterations {
+                let temperature = self.schedule.temperature(initial, last, step, n_iterations);
+
+                let change = match neighbourhood.random_move(&mut self.rng, &plan) {
+                    Some(change) => change,
+                    // Everyone we tried to move is pinned in place.
+                    None => continue,
+                };
+                let new_fitness = scorer.propose(&mut plan, &change);
+
+                let accept = if new_fitness >= current_fitness {
+                    true
+                } else if new_fitness.0 < current_fitness.0 {
+                    // Breaks more hard constraints.
+                    false
+                } else {
+                    let delta = new_fitness.1.to_f64() - current_fitness.1.to_f64();
+                    self.rng.gen_bool((delta / temperature).exp())
+                };
+
+                if accept {
+                    current_fitness = new_fitness;
+                    scorer.keep(&mut plan, &change);
+                    if current_fitness > best_fitness {
+                        best_fitness = current_fitness;
+                        best.clone_from(&plan);
+                    }
+                } else {
+                    scorer.reject(&mut plan, &change);
+                }
+            }
+        }
+
+        check_feasible(best, problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::objective::{Maximin, Weighted};
+    use crate::test_util::{assert_feasible, assert_improves_on_start, constrained_wedding};
+
+    #[test]
+    fn schedules_go_from_initial_to_last() {
+        let schedules = [
+            CoolingSchedule::Geometric,
+            CoolingSchedule::Linear,
+            CoolingSchedule::LundyMees,
+        ];
+        for schedule in schedules {
+            let temperatures: Vec<f64> = (0..=100)
+                .map(|step| schedule.temperature(50.0, 0.5, step, 100))
+                .collect();
+            assert!((temperatures[0] - 50.0).abs() < 1e-9);
+            assert!((temperatures[100] - 0.5).abs() < 1e-9);
+            assert!(temperatures.windows(2).all(|pair| pair[1] < pair[0]));
+        }
+    }
+
+    #[test]
+    fn annealing_never_worse_than_start() {
+        let schedules = [
+            CoolingSchedule::Geometric,
+            CoolingSchedule::Linear,
+            CoolingSchedule::LundyMees,
+        ];
+        for (seed, schedule) in schedules.iter().enumerate() {
+            assert_improves_on_start(seed as u64, 20, 4, |rng| {
+                SimulatedAnnealingPlanner::new(rng)
+                    .with_schedule(*schedule)
+                    .with_iterations(NonZeroUsize::new(5000).unwrap())
+                    .with_reheats(1)
+            });
+        }
+    }
+
+    #[test]
+    fn annealing_respects_constraints() {
+        let problem = constrained_wedding(0);
+        let mut planner = SimulatedAnnealingPlanner::with_objective(
+            StdRng::seed_from_u64(0),
+            Weighted::new(TotalHappiness, 1.0, Maximin, 2.0),
+        )
+        .with_temperatures(10.0, 0.01);
+        let plan = planner.plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+    }
+}
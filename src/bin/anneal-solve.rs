@@ -0,0 +1,65 @@
+use std::num::NonZeroUsize;
+
+use dissertation::objective::{Maximin, NumericScore, Objective, ObjectiveKind, TotalHappiness};
+use dissertation::{
+    run_local_search, CoolingSchedule, GuestSampling, MoveMix, SimulatedAnnealingPlanner,
+};
+
+use anyhow::anyhow;
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total or maximin.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How to cool down: geometric, linear or lundy-mees.
+    #[structopt(long, default_value = "geometric")]
+    schedule: CoolingSchedule,
+    /// How many moves to try each time we cool down. Defaults to 1000 per guest.
+    #[structopt(long)]
+    iterations: Option<NonZeroUsize>,
+    /// How many times to heat the best plan back up and cool it again.
+    #[structopt(long, default_value = "0")]
+    reheats: usize,
+    /// Which random moves to try, with weights, e.g. swap=2,cycle=1,chain=1.
+    #[structopt(long, default_value = "swap")]
+    moves: MoveMix,
+    /// How to pick guests to move: uniform, unhappiest or most-to-gain.
+    #[structopt(long, default_value = "uniform")]
+    sampling: GuestSampling,
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    match opt.objective {
+        ObjectiveKind::Total => solve(&opt, TotalHappiness),
+        ObjectiveKind::Maximin => solve(&opt, Maximin),
+        // Annealing needs to know how much worse a move is, and there's
+        // no sensible way to measure that between lexicographic scores.
+        ObjectiveKind::Fairness => Err(anyhow!(
+            "Simulated annealing can't optimise the fairness objective."
+        )),
+    }
+}
+
+fn solve<O>(opt: &Opt, objective: O) -> anyhow::Result<()>
+where
+    O: Objective,
+    O::Score: NumericScore,
+{
+    let mut solver = SimulatedAnnealingPlanner::with_objective(thread_rng(), objective)
+        .with_schedule(opt.schedule)
+        .with_reheats(opt.reheats)
+        .with_moves(opt.moves)
+        .with_sampling(opt.sampling);
+    if let Some(iterations) = opt.iterations {
+        solver = solver.with_iterations(iterations);
+    }
+    run_local_search(solver, opt.greedy_start)
+}
@@ -0,0 +1,78 @@
+use dissertation::{
+    run, BranchAndBoundPlanner, Plan, PlanError, Problem, SeatingPlanner, Solution,
+};
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// Stop after looking at this many partial plans.
+    #[structopt(long)]
+    node_limit: Option<u64>,
+    /// Stop after this many seconds.
+    #[structopt(long)]
+    time_limit: Option<f64>,
+    /// Add a line to this file saying whether the plan is optimal, or how
+    /// far from it the plan could be.
+    #[structopt(long, parse(from_os_str))]
+    report: Option<PathBuf>,
+}
+
+// Keeps the solution, so we can report on it once the plan is written.
+struct Reporting {
+    planner: BranchAndBoundPlanner,
+    solution: Option<Solution>,
+}
+
+impl SeatingPlanner for Reporting {
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let solution = self.planner.solve(problem)?;
+        let plan = solution.plan.clone();
+        self.solution = Some(solution);
+        Ok(plan)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    let mut planner = BranchAndBoundPlanner::new();
+    if let Some(node_limit) = opt.node_limit {
+        planner = planner.with_node_limit(node_limit);
+    }
+    if let Some(time_limit) = opt.time_limit {
+        planner = planner.with_time_limit(Duration::from_secs_f64(time_limit));
+    }
+
+    let mut reporting = Reporting {
+        planner,
+        solution: None,
+    };
+    run(&mut reporting)?;
+
+    if let (Some(path), Some(solution)) = (opt.report, reporting.solution) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Could not open report file {}.", path.display()))?;
+        if solution.is_optimal() {
+            writeln!(file, "optimal: {}", solution.total_happiness)?;
+        } else {
+            writeln!(
+                file,
+                "total happiness {}, upper bound {}, gap {}",
+                solution.total_happiness,
+                solution.upper_bound,
+                solution.gap()
+            )?;
+        }
+    }
+    Ok(())
+}
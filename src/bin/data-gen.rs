@@ -1,3 +1,6 @@
+// Index loops read more naturally than iterators when filling in a matrix.
+#![allow(clippy::needless_range_loop)]
+
 use std::fs::File;
 use std::io::{self, Write};
 use std::ops::Range;
@@ -52,16 +55,14 @@ fn main() -> anyhow::Result<()> {
 }
 
 fn run(opt: Opt) -> anyhow::Result<()> {
-    let relations = match opt.method {
+    let matrix = match opt.method {
         GenerationMethod::Random => random_relations(opt.n_tables * opt.table_size),
         GenerationMethod::CompleteComponents => complete_components(opt.n_tables, opt.table_size),
         GenerationMethod::Rings => rings(opt.n_tables, opt.table_size),
         GenerationMethod::Tense => tense(opt.n_tables * opt.table_size),
     };
-    let problem = Problem {
-        relations,
-        n_tables: opt.n_tables,
-    };
+    let relations = GuestRelations::new(matrix)?;
+    let problem = Problem::new(relations, opt.n_tables)?;
 
     let mut out: Box<dyn Write> = match opt.output {
         None => Box::new(io::stdout()),
@@ -73,7 +74,7 @@ fn run(opt: Opt) -> anyhow::Result<()> {
 
 // Tense weddings where people have strong feelings about other guests,
 // positive or negative.
-fn tense(n_guests: usize) -> GuestRelations {
+fn tense(n_guests: usize) -> Vec<Vec<i64>> {
     let mut rng = thread_rng();
     let mut relations = vec![vec![0; n_guests]; n_guests];
 
@@ -89,11 +90,11 @@ fn tense(n_guests: usize) -> GuestRelations {
         }
     }
 
-    GuestRelations::new(relations)
+    relations
 }
 
 /// Tables where everyone knows each other.
-fn complete_components(n_tables: usize, table_size: usize) -> GuestRelations {
+fn complete_components(n_tables: usize, table_size: usize) -> Vec<Vec<i64>> {
     let n_guests = n_tables * table_size;
     let mut relations = vec![vec![0; n_guests]; n_guests];
 
@@ -109,10 +110,10 @@ fn complete_components(n_tables: usize, table_size: usize) -> GuestRelations {
         relations[i][i] = 0;
     }
 
-    GuestRelations::new(relations)
+    relations
 }
 
-fn rings(n_tables: usize, table_size: usize) -> GuestRelations {
+fn rings(n_tables: usize, table_size: usize) -> Vec<Vec<i64>> {
     let n_guests = n_tables * table_size;
     let mut relations = vec![vec![0; n_guests]; n_guests];
 
@@ -124,16 +125,16 @@ fn rings(n_tables: usize, table_size: usize) -> GuestRelations {
         }
     }
 
-    GuestRelations::new(relations)
+    relations
 }
 
-fn random_relations(n_guests: usize) -> GuestRelations {
+fn random_relations(n_guests: usize) -> Vec<Vec<i64>> {
     let mut friend_lists = random_friend_lists(n_guests);
     friends_of_friends(&mut friend_lists);
 
     let mut relationships = vec![vec![0; n_guests]; n_guests];
     fill_adj_matrix(&friend_lists, 1, &mut relationships);
-    GuestRelations::new(relationships)
+    relationships
 }
 
 fn random_friend_lists(n_guests: usize) -> Vec<Vec<usize>> {
@@ -0,0 +1,20 @@
+use dissertation::{run, SubsetDpPlanner};
+
+use anyhow::Context;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// Refuse problems that would need more than this many MiB of memory.
+    #[structopt(long, default_value = "1024")]
+    memory_limit: usize,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+    let memory_limit = opt
+        .memory_limit
+        .checked_mul(1 << 20)
+        .context("The memory limit is too large.")?;
+    run(SubsetDpPlanner::new().with_memory_limit(memory_limit))
+}
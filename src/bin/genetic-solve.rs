@@ -0,0 +1,56 @@
+use std::num::NonZeroUsize;
+
+use dissertation::objective::{Objective, ObjectiveKind, WithObjective};
+use dissertation::{run_local_search, ClimbMode, GeneticPlanner};
+
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total, maximin or fairness.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How many plans are in each generation.
+    #[structopt(long, default_value = "50")]
+    population: NonZeroUsize,
+    /// How many generations without finding a better plan before stopping.
+    #[structopt(long, default_value = "50")]
+    patience: usize,
+    /// The probability that each child has a random move made to it.
+    #[structopt(long, default_value = "0.3", parse(try_from_str = parse_probability))]
+    mutation_rate: f64,
+    /// Hill climb every child (stochastic, first or steepest), making the search memetic.
+    #[structopt(long)]
+    local_search: Option<ClimbMode>,
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
+
+fn parse_probability(s: &str) -> anyhow::Result<f64> {
+    let p: f64 = s.parse()?;
+    anyhow::ensure!((0.0..=1.0).contains(&p), "{} is not between 0 and 1", p);
+    Ok(p)
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    opt.objective.apply(&opt)
+}
+
+impl WithObjective for &Opt {
+    type Output = anyhow::Result<()>;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output {
+        let mut solver = GeneticPlanner::with_objective(thread_rng(), objective)
+            .with_population_size(self.population)
+            .with_patience(self.patience)
+            .with_mutation_rate(self.mutation_rate);
+        if let Some(mode) = self.local_search {
+            solver = solver.with_local_search(mode);
+        }
+        run_local_search(solver, self.greedy_start)
+    }
+}
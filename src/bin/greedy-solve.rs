@@ -0,0 +1,15 @@
+use dissertation::{run, GreedyPlanner, Insertion};
+
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// Who to seat next: best (fill one table at a time) or regret.
+    #[structopt(long, default_value = "best")]
+    insertion: Insertion,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+    run(GreedyPlanner::new(opt.insertion))
+}
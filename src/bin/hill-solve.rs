@@ -1,9 +1,47 @@
-use dissertation::{run, HillClimbingPlanner};
+use dissertation::objective::{Objective, ObjectiveKind, WithObjective};
+use dissertation::{run_local_search, ClimbMode, GuestSampling, HillClimbingPlanner, MoveMix};
 
 use ordered_float::NotNan;
 use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total, maximin or fairness.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How to choose improving moves: stochastic, first or steepest.
+    #[structopt(long, default_value = "stochastic")]
+    mode: ClimbMode,
+    /// Which random moves to try, with weights, e.g. swap=2,cycle=1,chain=1.
+    #[structopt(long, default_value = "swap")]
+    moves: MoveMix,
+    /// How to pick guests to move: uniform, unhappiest or most-to-gain.
+    #[structopt(long, default_value = "uniform")]
+    sampling: GuestSampling,
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
 
 fn main() -> anyhow::Result<()> {
-    let solver = HillClimbingPlanner::new(thread_rng(), NotNan::new(0.001).unwrap());
-    run(solver)
+    let opt = Opt::from_args();
+
+    opt.objective.apply(&opt)
+}
+
+impl WithObjective for &Opt {
+    type Output = anyhow::Result<()>;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output {
+        let solver = HillClimbingPlanner::with_objective(
+            thread_rng(),
+            NotNan::new(0.001).unwrap(),
+            objective,
+        )
+        .with_mode(self.mode)
+        .with_moves(self.moves)
+        .with_sampling(self.sampling);
+        run_local_search(solver, self.greedy_start)
+    }
 }
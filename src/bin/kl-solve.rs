@@ -0,0 +1,18 @@
+use dissertation::{run_local_search, KernighanLinPlanner};
+
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    let solver = KernighanLinPlanner::new(thread_rng());
+    run_local_search(solver, opt.greedy_start)
+}
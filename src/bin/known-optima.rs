@@ -0,0 +1,72 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use dissertation::metrics::Metrics;
+use dissertation::{PlanError, Problem, SeatingPlanner, SubsetDpPlanner};
+
+use anyhow::Context;
+use serde::Serialize;
+use structopt::StructOpt;
+
+/// Solves every problem in a suite that is small enough to solve exactly,
+/// and writes the optimal total happiness to `<suite>-optima.csv`.
+#[derive(StructOpt)]
+struct Opt {
+    suites: Vec<PathBuf>,
+    /// Skip problems that would need more than this many MiB of memory.
+    #[structopt(long, default_value = "1024")]
+    memory_limit: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Record {
+    wedding: PathBuf,
+    n_people: usize,
+    n_tables: usize,
+    total_happiness: i64,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+    let memory_limit = opt
+        .memory_limit
+        .checked_mul(1 << 20)
+        .context("The memory limit is too large.")?;
+    let mut planner = SubsetDpPlanner::new().with_memory_limit(memory_limit);
+
+    for suite in &opt.suites {
+        let mut weddings: Vec<PathBuf> = fs::read_dir(suite)
+            .with_context(|| format!("Could not open directory {:?}.", suite))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .collect();
+        weddings.sort_unstable();
+
+        let mut name = suite.file_name().unwrap().to_owned();
+        name.push("-optima.csv");
+        let mut writer = csv::Writer::from_path(suite.with_file_name(name))?;
+
+        for wedding in weddings {
+            let file = File::open(&wedding)
+                .with_context(|| format!("Could not open problem file: {:?}", wedding))?;
+            let problem: Problem = serde_json::from_reader(file)
+                .with_context(|| format!("Could not parse problem: {:?}", wedding))?;
+
+            let plan = match planner.plan(&problem) {
+                Ok(plan) => plan,
+                // Too big to solve exactly, so the optimum stays unknown.
+                Err(PlanError::Unsupported(_)) => continue,
+                Err(e) => return Err(e).with_context(|| format!("Could not solve {:?}", wedding)),
+            };
+            writer.serialize(Record {
+                wedding,
+                n_people: problem.relations().len(),
+                n_tables: problem.n_tables,
+                total_happiness: Metrics::new(&plan, &problem).total_happiness(),
+            })?;
+        }
+        writer.flush()?;
+    }
+
+    Ok(())
+}
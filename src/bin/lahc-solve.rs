@@ -1,9 +1,46 @@
-use dissertation::{run, LahcPlanner};
+use dissertation::objective::{Objective, ObjectiveKind, WithObjective};
+use dissertation::{run_local_search, GuestSampling, LahcPlanner, MoveMix};
+
+use std::num::NonZeroUsize;
 
 use ordered_float::NotNan;
 use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total, maximin or fairness.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How many iterations back a new plan is compared against.
+    #[structopt(long, default_value = "1000")]
+    history: NonZeroUsize,
+    /// Which random moves to try, with weights, e.g. swap=2,cycle=1,chain=1.
+    #[structopt(long, default_value = "swap")]
+    moves: MoveMix,
+    /// How to pick guests to move: uniform, unhappiest or most-to-gain.
+    #[structopt(long, default_value = "uniform")]
+    sampling: GuestSampling,
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
 
 fn main() -> anyhow::Result<()> {
-    let solver = LahcPlanner::new(thread_rng(), NotNan::new(0.001).unwrap());
-    run(solver)
+    let opt = Opt::from_args();
+
+    opt.objective.apply(&opt)
+}
+
+impl WithObjective for &Opt {
+    type Output = anyhow::Result<()>;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output {
+        let solver =
+            LahcPlanner::with_objective(thread_rng(), NotNan::new(0.001).unwrap(), objective)
+                .with_history_length(self.history)
+                .with_moves(self.moves)
+                .with_sampling(self.sampling);
+        run_local_search(solver, self.greedy_start)
+    }
 }
@@ -0,0 +1,44 @@
+use std::num::NonZeroUsize;
+
+use dissertation::objective::{Objective, ObjectiveKind, WithObjective};
+use dissertation::{run_local_search, LnsPlanner, Repair};
+
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total, maximin or fairness.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How to re-seat unseated guests: greedy or exact.
+    #[structopt(long, default_value = "greedy")]
+    repair: Repair,
+    /// Roughly how many guests to unseat each step.
+    #[structopt(long, default_value = "8")]
+    destroy_size: NonZeroUsize,
+    /// How many steps without finding a better plan before stopping.
+    #[structopt(long, default_value = "200")]
+    patience: usize,
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    opt.objective.apply(&opt)
+}
+
+impl WithObjective for &Opt {
+    type Output = anyhow::Result<()>;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output {
+        let solver = LnsPlanner::with_objective(thread_rng(), objective)
+            .with_repair(self.repair)
+            .with_destroy_size(self.destroy_size)
+            .with_patience(self.patience);
+        run_local_search(solver, self.greedy_start)
+    }
+}
@@ -0,0 +1,33 @@
+use dissertation::objective::{Objective, ObjectiveKind, WithObjective};
+use dissertation::{run, ClimbMode, MultilevelPlanner};
+
+use ordered_float::NotNan;
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total, maximin or fairness.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How to choose improving moves at each level: stochastic, first or steepest.
+    #[structopt(long, default_value = "stochastic")]
+    mode: ClimbMode,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    opt.objective.apply(&opt)
+}
+
+impl WithObjective for &Opt {
+    type Output = anyhow::Result<()>;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output {
+        run(
+            MultilevelPlanner::with_objective(thread_rng(), NotNan::new(0.001).unwrap(), objective)
+                .with_mode(self.mode),
+        )
+    }
+}
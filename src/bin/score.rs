@@ -14,6 +14,9 @@ use structopt::StructOpt;
 struct Opt {
     solver: PathBuf,
     problem: PathBuf,
+    /// Extra arguments passed on to the solver, such as `-- --objective fairness`.
+    #[structopt(last = true)]
+    solver_args: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -31,6 +34,12 @@ struct Record {
     max_happiness: i64,
     n_lonely: usize,
 
+    // Hard constraints the solution breaks, which should always be zero.
+    together_violations: usize,
+    apart_violations: usize,
+    pin_violations: usize,
+    friendless_violations: usize,
+
     // Time spent on the problem.
     // Can't use `Duration` becuase this is going into a csv.
     seconds: f64,
@@ -48,9 +57,13 @@ fn main() -> anyhow::Result<()> {
 }
 
 fn run(opt: Opt) -> anyhow::Result<()> {
-    let records = score_path(&opt.solver, &opt.problem)?;
+    let solver = Solver {
+        path: &opt.solver,
+        args: &opt.solver_args,
+    };
+    let records = score_path(solver, &opt.problem)?;
 
-    let out_file = create_out_file(&opt.solver, &opt.problem)?;
+    let out_file = create_out_file(solver, &opt.problem)?;
     let mut writer = csv::Writer::from_writer(out_file);
 
     for record in records {
@@ -60,11 +73,23 @@ fn run(opt: Opt) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn create_out_file(solver: &Path, problem: &Path) -> anyhow::Result<File> {
-    let solver_name = solver.file_stem().unwrap();
+// A solver binary, and the arguments to run it with.
+#[derive(Debug, Copy, Clone)]
+struct Solver<'a> {
+    path: &'a Path,
+    args: &'a [String],
+}
+
+fn create_out_file(solver: Solver, problem: &Path) -> anyhow::Result<File> {
+    let solver_name = solver.path.file_stem().unwrap();
     let problem_name = problem.file_stem().unwrap();
 
+    // Runs of the same solver with different arguments get different files.
     let mut csv_name = solver_name.to_owned();
+    for arg in solver.args {
+        csv_name.push("-");
+        csv_name.push(arg.trim_start_matches('-'));
+    }
     csv_name.push("_");
     csv_name.push(problem_name);
 
@@ -72,7 +97,7 @@ fn create_out_file(solver: &Path, problem: &Path) -> anyhow::Result<File> {
     File::create(&path).with_context(move || format!("Could not create output file: {:?}", path))
 }
 
-fn score_path(solver: &Path, problem: &Path) -> anyhow::Result<Vec<Record>> {
+fn score_path(solver: Solver, problem: &Path) -> anyhow::Result<Vec<Record>> {
     if problem.is_file() {
         const N_RUNS: usize = 10;
 
@@ -81,7 +106,7 @@ fn score_path(solver: &Path, problem: &Path) -> anyhow::Result<Vec<Record>> {
                 let wedding_file = File::open(problem)
                     .with_context(|| format!("Could not open problem file: {:?}", problem))?;
                 score_single(solver, &wedding_file, problem.to_owned()).with_context(|| {
-                    format!("Could not run {:?} on wedding {:?}.", solver, problem)
+                    format!("Could not run {:?} on wedding {:?}.", solver.path, problem)
                 })
             })
             .collect();
@@ -101,12 +126,13 @@ fn score_path(solver: &Path, problem: &Path) -> anyhow::Result<Vec<Record>> {
 }
 
 fn score_single(
-    solver: &Path,
+    solver: Solver,
     mut wedding: &File,
     wedding_name: PathBuf,
 ) -> anyhow::Result<Record> {
     // Create the solver as a child process.
-    let mut solver = Command::new(&solver)
+    let mut solver = Command::new(solver.path)
+        .args(solver.args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -144,17 +170,20 @@ fn score_single(
     let duration = time_begin.elapsed();
 
     if !output.stderr.is_empty() {
-        return Err(anyhow!("Solver experienced a problem."));
+        return Err(anyhow!(
+            "Solver experienced a problem: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
 
     let plan: Plan = serde_json::from_slice(&output.stdout)
         .with_context(|| "Could not parse output from solver.")?;
 
     // Find out how good the solution is and return.
-    let metrics = Metrics::new(&plan, &problem_data.relations);
+    let metrics = Metrics::new(&plan, &problem_data);
     let score = Record {
         wedding: wedding_name,
-        n_people: problem_data.relations.len(),
+        n_people: problem_data.relations().len(),
         n_tables: problem_data.n_tables,
         total_happiness: metrics.total_happiness(),
         mean_happiness: metrics.mean_happiness(),
@@ -162,12 +191,16 @@ fn score_single(
         min_happiness: metrics.min_happiness(),
         max_happiness: metrics.max_happiness(),
         n_lonely: metrics.n_lonely(),
+        together_violations: metrics.together_violations(),
+        apart_violations: metrics.apart_violations(),
+        pin_violations: metrics.pin_violations(),
+        friendless_violations: metrics.friendless_violations(),
         seconds: duration.as_secs_f64(),
     };
     Ok(score)
 }
 
-fn score_suite<I, E>(solver: &Path, suite: I) -> anyhow::Result<Vec<Record>>
+fn score_suite<I, E>(solver: Solver, suite: I) -> anyhow::Result<Vec<Record>>
 where
     I: Iterator<Item = Result<DirEntry, E>>,
 {
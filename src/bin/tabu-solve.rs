@@ -0,0 +1,54 @@
+use std::num::NonZeroUsize;
+
+use dissertation::objective::{Objective, ObjectiveKind, WithObjective};
+use dissertation::{run_local_search, GuestSampling, MoveMix, TabuSearchPlanner};
+
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Opt {
+    /// What to optimise: total, maximin or fairness.
+    #[structopt(long, default_value = "total")]
+    objective: ObjectiveKind,
+    /// How many iterations a guest who was moved must stay put.
+    #[structopt(long, default_value = "10")]
+    tenure: usize,
+    /// How many random moves to look at each iteration. Looks at every move if missing.
+    #[structopt(long)]
+    sample: Option<NonZeroUsize>,
+    /// How many iterations without finding a better plan before stopping.
+    #[structopt(long, default_value = "100")]
+    patience: usize,
+    /// Which random moves to sample, with weights, e.g. swap=2,cycle=1,chain=1.
+    #[structopt(long, default_value = "swap")]
+    moves: MoveMix,
+    /// How to pick guests to move: uniform, unhappiest or most-to-gain.
+    #[structopt(long, default_value = "uniform")]
+    sampling: GuestSampling,
+    /// Start from a greedy plan rather than a random one.
+    #[structopt(long)]
+    greedy_start: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    opt.objective.apply(&opt)
+}
+
+impl WithObjective for &Opt {
+    type Output = anyhow::Result<()>;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output {
+        let mut solver = TabuSearchPlanner::with_objective(thread_rng(), objective)
+            .with_tenure(self.tenure)
+            .with_patience(self.patience)
+            .with_moves(self.moves)
+            .with_sampling(self.sampling);
+        if let Some(sample) = self.sample {
+            solver = solver.with_sample_size(sample);
+        }
+        run_local_search(solver, self.greedy_start)
+    }
+}
@@ -0,0 +1,406 @@
+use crate::metrics::Metrics;
+use crate::{GreedyPlanner, Plan, PlanError, Problem, SeatingPlanner};
+
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+/// What an exact planner found: a plan, and how far from the best
+/// possible total happiness it could be.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Solution {
+    pub plan: Plan,
+    pub total_happiness: i64,
+    /// No plan has more total happiness than this.
+    pub upper_bound: i64,
+}
+
+impl Solution {
+    /// Returns `true` if the plan is proven to be optimal.
+    pub fn is_optimal(&self) -> bool {
+        self.total_happiness >= self.upper_bound
+    }
+
+    /// Returns how much more total happiness a plan could possibly have.
+    pub fn gap(&self) -> i64 {
+        self.upper_bound - self.total_happiness
+    }
+}
+
+/// Finds the plan with the most total happiness by searching every way of
+/// seating the guests, skipping any part of the search that can't beat the
+/// best plan found so far. Tables that only differ in which number they
+/// have are treated as the same.
+///
+/// Without limits, the plan is optimal. With them, the search may stop
+/// early, and `solve` says how far from optimal the plan could be.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct BranchAndBoundPlanner {
+    node_limit: Option<u64>,
+    time_limit: Option<Duration>,
+}
+
+impl BranchAndBoundPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops after looking at this many partial plans.
+    pub fn with_node_limit(self, node_limit: u64) -> Self {
+        Self {
+            node_limit: Some(node_limit),
+            ..self
+        }
+    }
+
+    /// Stops after searching for this long.
+    pub fn with_time_limit(self, time_limit: Duration) -> Self {
+        Self {
+            time_limit: Some(time_limit),
+            ..self
+        }
+    }
+
+    /// Finds a plan, along with how far from optimal it could be.
+    pub fn solve(&mut self, problem: &Problem) -> Result<Solution, PlanError> {
+        if problem.layout.is_some() {
+            return Err(PlanError::Unsupported(
+                "branch and bound doesn't know about seating layouts",
+            ));
+        }
+
+        let mut search = Search::new(problem, self.node_limit, self.time_limit);
+
+        // A quick plan to beat makes a lot of the search unnecessary.
+        if let Ok(plan) = GreedyPlanner::default().plan(problem) {
+            let total = Metrics::new(&plan, problem).total_happiness();
+            search.incumbent = Some((total, plan));
+        }
+
+        search.branch(0);
+
+        let (total_happiness, plan) = search.incumbent.ok_or(PlanError::NotFound)?;
+        let upper_bound = search
+            .open_bound
+            .map_or(total_happiness, |bound| bound.max(total_happiness));
+        Ok(Solution {
+            plan,
+            total_happiness,
+            upper_bound,
+        })
+    }
+}
+
+impl SeatingPlanner for BranchAndBoundPlanner {
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        self.solve(problem).map(|solution| solution.plan)
+    }
+}
+
+struct Search<'a> {
+    problem: &'a Problem,
+    // The groups who must sit together, most connected first, which
+    // makes good plans turn up early.
+    units: Vec<Vec<usize>>,
+    // Index by unit to get the happiness among the group.
+    unit_happiness: Vec<i64>,
+    // Index by table to get the lowest-numbered table it is
+    // interchangeable with.
+    class_of: Vec<usize>,
+    apart_from: Vec<Vec<usize>>,
+    // Index by guest, then by `k`, to get the sum of their `k` best
+    // relationships.
+    best_relationships: Vec<Vec<i64>>,
+    // The most people anyone could share a table with.
+    max_tablemates: usize,
+
+    plan: Plan,
+    table_of: Vec<Option<usize>>,
+    n_seated: usize,
+    total: i64,
+
+    incumbent: Option<(i64, Plan)>,
+    n_nodes: u64,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    // If the search stopped early, the best any unexplored plan could do.
+    open_bound: Option<i64>,
+    stopped: bool,
+}
+
+impl<'a> Search<'a> {
+    fn new(problem: &'a Problem, node_limit: Option<u64>, time_limit: Option<Duration>) -> Self {
+        let relations = problem.relations();
+        let n_guests = relations.len();
+
+        let best_relationships: Vec<Vec<i64>> = (0..n_guests)
+            .map(|guest| {
+                let mut positives: Vec<i64> = (0..n_guests)
+                    .map(|other| relations.relationship(guest, other))
+                    .filter(|r| *r > 0)
+                    .collect();
+                positives.sort_unstable_by_key(|r| Reverse(*r));
+                positives.resize(n_guests, 0);
+                std::iter::once(0)
+                    .chain(positives.iter().scan(0, |sum, r| {
+                        *sum += r;
+                        Some(*sum)
+                    }))
+                    .collect()
+            })
+            .collect();
+        let max_tablemates = problem
+            .capacities()
+            .max()
+            .unwrap_or(1)
+            .min(n_guests)
+            .saturating_sub(1);
+
+        let mut units = problem.units();
+        units.sort_by_cached_key(|unit| {
+            let potential: i64 = unit
+                .iter()
+                .map(|guest| best_relationships[*guest][max_tablemates])
+                .sum();
+            Reverse(potential)
+        });
+        let unit_happiness = units
+            .iter()
+            .map(|unit| {
+                let pairs = unit.iter().flat_map(|g| unit.iter().map(move |h| (*g, *h)));
+                pairs.map(|(g, h)| relations.relationship(g, h)).sum()
+            })
+            .collect();
+
+        let mut apart_from = vec![Vec::new(); n_guests];
+        for &(i, j) in problem.apart() {
+            apart_from[i].push(j);
+            apart_from[j].push(i);
+        }
+
+        Self {
+            problem,
+            units,
+            unit_happiness,
+            class_of: problem.table_classes(),
+            apart_from,
+            best_relationships,
+            max_tablemates,
+            plan: vec![Vec::new(); problem.n_tables],
+            table_of: vec![None; n_guests],
+            n_seated: 0,
+            total: 0,
+            incumbent: None,
+            n_nodes: 0,
+            node_limit,
+            deadline: time_limit.map(|limit| Instant::now() + limit),
+            open_bound: None,
+            stopped: false,
+        }
+    }
+
+    // Seats the units from `depth` onwards in every way that might beat
+    // the best plan so far.
+    fn branch(&mut self, depth: usize) {
+        if depth == self.units.len() {
+            self.try_complete_plan();
+            return;
+        }
+
+        let bound = self.upper_bound();
+        if let Some((best, _)) = &self.incumbent {
+            if bound <= *best {
+                return;
+            }
+        }
+
+        self.n_nodes += 1;
+        let out_of_nodes = self.node_limit.is_some_and(|limit| self.n_nodes > limit);
+        let out_of_time = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() > deadline);
+        if out_of_nodes || out_of_time {
+            self.stopped = true;
+        }
+        if self.stopped {
+            self.open_bound = Some(self.open_bound.map_or(bound, |b| b.max(bound)));
+            return;
+        }
+
+        let unit = depth;
+        let mut tables: Vec<(i64, usize)> = (0..self.plan.len())
+            .filter(|t| self.can_seat(unit, *t))
+            .map(|t| (self.gain(unit, t), t))
+            .collect();
+        tables.sort_by_key(|(gain, t)| (Reverse(*gain), *t));
+
+        for (i, (gain, table)) in tables.iter().enumerate() {
+            self.seat(unit, *table, *gain);
+            self.branch(depth + 1);
+            self.unseat(unit, *table, *gain);
+
+            if self.stopped {
+                if i + 1 < tables.len() {
+                    // Some tables were never tried.
+                    self.open_bound = Some(self.open_bound.map_or(bound, |b| b.max(bound)));
+                }
+                return;
+            }
+        }
+    }
+
+    fn try_complete_plan(&mut self) {
+        if let Some((best, _)) = &self.incumbent {
+            if self.total <= *best {
+                return;
+            }
+        }
+        // Everything else was checked while seating, but needing a friend
+        // can only be checked once everyone is seated.
+        if Metrics::new(&self.plan, self.problem).n_violations() == 0 {
+            self.incumbent = Some((self.total, self.plan.clone()));
+        }
+    }
+
+    fn can_seat(&self, unit: usize, table: usize) -> bool {
+        let problem = self.problem;
+        let unit = &self.units[unit];
+
+        // Only try the first of the empty tables that are interchangeable.
+        if self.plan[table].is_empty()
+            && (0..table)
+                .any(|t| self.plan[t].is_empty() && self.class_of[t] == self.class_of[table])
+        {
+            return false;
+        }
+
+        let fits = self.plan[table].len() + unit.len() <= problem.capacity(table);
+        let allowed = unit.iter().all(|guest| problem.allows(*guest, table));
+        let conflict = unit
+            .iter()
+            .flat_map(|guest| &self.apart_from[*guest])
+            .any(|other| self.table_of[*other] == Some(table));
+
+        // There must be enough people left to bring every table up to
+        // its minimum occupancy.
+        let n_left = problem.relations().len() - self.n_seated - unit.len();
+        let n_needed: usize = (0..self.plan.len())
+            .map(|t| {
+                let seated = self.plan[t].len() + if t == table { unit.len() } else { 0 };
+                problem.min_occupancy.saturating_sub(seated)
+            })
+            .sum();
+
+        fits && allowed && !conflict && n_needed <= n_left
+    }
+
+    // How much total happiness goes up by seating the unit at the table.
+    fn gain(&self, unit: usize, table: usize) -> i64 {
+        let relations = self.problem.relations();
+        let with_table: i64 = self.units[unit]
+            .iter()
+            .flat_map(|guest| self.plan[table].iter().map(move |other| (*guest, *other)))
+            .map(|(guest, other)| relations.relationship(guest, other))
+            .sum();
+        2 * with_table + self.unit_happiness[unit]
+    }
+
+    fn seat(&mut self, unit: usize, table: usize, gain: i64) {
+        for guest in &self.units[unit] {
+            self.plan[table].push(*guest);
+            self.table_of[*guest] = Some(table);
+        }
+        self.n_seated += self.units[unit].len();
+        self.total += gain;
+    }
+
+    fn unseat(&mut self, unit: usize, table: usize, gain: i64) {
+        for guest in &self.units[unit] {
+            self.plan[table].pop();
+            self.table_of[*guest] = None;
+        }
+        self.n_seated -= self.units[unit].len();
+        self.total -= gain;
+    }
+
+    // Nobody can gain more than the sum of their best relationships with
+    // the people who could still join their table.
+    fn upper_bound(&self) -> i64 {
+        let n_unseated = self.problem.relations().len() - self.n_seated;
+        let potential: i64 = self
+            .table_of
+            .iter()
+            .enumerate()
+            .map(|(guest, table)| {
+                let n_new_tablemates = match table {
+                    Some(table) => {
+                        let free = self.problem.capacity(*table) - self.plan[*table].len();
+                        free.min(n_unseated)
+                    }
+                    None => self.max_tablemates,
+                };
+                self.best_relationships[guest][n_new_tablemates]
+            })
+            .sum();
+        self.total + potential
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+
+    use crate::test_util::{assert_feasible, constrained_wedding, random_wedding};
+
+    // Tries every way of splitting eight guests into two tables of four.
+    fn best_split(problem: &Problem) -> i64 {
+        (0..1u32 << 8)
+            .filter(|mask| mask.count_ones() == 4)
+            .map(|mask| {
+                let (first, second) = (0..8).partition(|guest| mask & (1 << guest) != 0);
+                Metrics::new(&vec![first, second], problem).total_happiness()
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn branch_and_bound_is_optimal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            let problem = random_wedding(&mut rng, 8, 2);
+            let solution = BranchAndBoundPlanner::new().solve(&problem).unwrap();
+
+            assert!(solution.is_optimal());
+            assert_eq!(solution.total_happiness, best_split(&problem));
+            assert_eq!(
+                Metrics::new(&solution.plan, &problem).total_happiness(),
+                solution.total_happiness
+            );
+        }
+    }
+
+    #[test]
+    fn branch_and_bound_respects_constraints() {
+        let problem = constrained_wedding(1);
+        let solution = BranchAndBoundPlanner::new().solve(&problem).unwrap();
+        assert!(solution.is_optimal());
+        assert_feasible(&solution.plan, &problem);
+    }
+
+    #[test]
+    fn node_limit_reports_gap() {
+        let problem = random_wedding(&mut StdRng::seed_from_u64(2), 24, 4);
+        let solution = BranchAndBoundPlanner::new()
+            .with_node_limit(100)
+            .solve(&problem)
+            .unwrap();
+        assert!(solution.gap() >= 0);
+        assert!(!solution.is_optimal());
+        assert_eq!(
+            Metrics::new(&solution.plan, &problem).total_happiness(),
+            solution.total_happiness
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::hill_climb::{check_feasible, fitness};
+use crate::metrics::Metrics;
+use crate::objective::{Objective, TotalHappiness};
+use crate::{Plan, PlanError, Problem, SeatingPlanner};
+
+use std::cmp::Reverse;
+
+/// The most guests `BruteForcePlanner` will try to seat.
+pub const MAX_BRUTE_FORCE_GUESTS: usize = 12;
+
+/// Finds the best plan by trying every way of splitting the guests between
+/// the tables, counting tables that only differ in which number they have
+/// once. This takes exponential time, so it only accepts tiny problems,
+/// and is mostly useful for checking other planners.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct BruteForcePlanner<O = TotalHappiness> {
+    objective: O,
+}
+
+impl BruteForcePlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<O> BruteForcePlanner<O>
+where
+    O: Objective,
+{
+    pub fn with_objective(objective: O) -> Self {
+        Self { objective }
+    }
+}
+
+impl<O> SeatingPlanner for BruteForcePlanner<O>
+where
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        if problem.relations().len() > MAX_BRUTE_FORCE_GUESTS {
+            return Err(PlanError::Unsupported(
+                "brute force only seats up to 12 guests",
+            ));
+        }
+        if problem.layout.is_some() {
+            return Err(PlanError::Unsupported(
+                "brute force doesn't know about seating layouts",
+            ));
+        }
+
+        let mut search = Search {
+            problem,
+            objective: &self.objective,
+            class_of: problem.table_classes(),
+            plan: vec![Vec::new(); problem.n_tables],
+            best: None,
+        };
+        search.seat_from(0);
+
+        let (_, plan) = search.best.ok_or(PlanError::Unpackable)?;
+        check_feasible(plan, problem)
+    }
+}
+
+type Fitness<O> = (Reverse<usize>, <O as Objective>::Score);
+
+struct Search<'a, O: Objective> {
+    problem: &'a Problem,
+    objective: &'a O,
+    // Index by table to get the lowest-numbered table it is
+    // interchangeable with.
+    class_of: Vec<usize>,
+    plan: Plan,
+    best: Option<(Fitness<O>, Plan)>,
+}
+
+impl<O: Objective> Search<'_, O> {
+    // Tries every table for the guest, and every way of seating the
+    // guests after them.
+    fn seat_from(&mut self, guest: usize) {
+        let problem = self.problem;
+        if guest == problem.relations().len() {
+            self.score_plan();
+            return;
+        }
+
+        for table in 0..self.plan.len() {
+            let full = self.plan[table].len() >= problem.capacity(table);
+            // Seating the guest at any other empty table of the same class
+            // gives the same plan with the tables renumbered.
+            let repeat = self.plan[table].is_empty()
+                && (0..table)
+                    .any(|t| self.plan[t].is_empty() && self.class_of[t] == self.class_of[table]);
+            if full || repeat {
+                continue;
+            }
+
+            self.plan[table].push(guest);
+            self.seat_from(guest + 1);
+            self.plan[table].pop();
+        }
+    }
+
+    fn score_plan(&mut self) {
+        let min_occupancy = self.problem.min_occupancy;
+        if self.plan.iter().any(|table| table.len() < min_occupancy) {
+            return;
+        }
+
+        let score = fitness(self.objective, &Metrics::new(&self.plan, self.problem));
+        if self.best.as_ref().is_none_or(|(best, _)| score > *best) {
+            self.best = Some((score, self.plan.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::objective::Maximin;
+    use crate::test_util::assert_feasible;
+    use crate::GuestRelations;
+
+    fn relations(n_guests: usize) -> GuestRelations {
+        // Everyone likes the guests whose number is close to theirs.
+        let matrix = (0..n_guests as i64)
+            .map(|i| {
+                (0..n_guests as i64)
+                    .map(|j| if i == j { 0 } else { 3 - (i - j).abs() })
+                    .collect()
+            })
+            .collect();
+        GuestRelations::new(matrix).unwrap()
+    }
+
+    #[test]
+    fn brute_force_finds_optimum() {
+        let problem = Problem::new(relations(9), 3).unwrap();
+        let plan = BruteForcePlanner::new().plan(&problem).unwrap();
+        // Each table of three in a row has two pairs who like each other 2
+        // and one who like each other 1, both ways round.
+        assert_eq!(Metrics::new(&plan, &problem).total_happiness(), 3 * 2 * 5);
+
+        let plan = BruteForcePlanner::with_objective(Maximin)
+            .plan(&problem)
+            .unwrap();
+        assert_eq!(Metrics::new(&plan, &problem).min_happiness(), 3);
+    }
+
+    #[test]
+    fn brute_force_respects_constraints() {
+        let problem = Problem::with_capacities(relations(8), vec![4, 3, 3])
+            .unwrap()
+            .with_min_occupancy(2)
+            .unwrap()
+            .with_constraints(vec![vec![0, 7]], vec![(3, 4)])
+            .unwrap()
+            .with_pins(vec![(5, 2)], vec![])
+            .unwrap();
+        let plan = BruteForcePlanner::new().plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+    }
+
+    #[test]
+    fn brute_force_refuses_big_problems() {
+        let problem = Problem::new(relations(13), 3).unwrap();
+        assert!(matches!(
+            BruteForcePlanner::new().plan(&problem),
+            Err(PlanError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::hill_climb::Swap;
+use crate::{Plan, Problem};
+
+/// Keeps track of everyone's happiness as swaps are made to a plan, so that
+/// a swap can be scored by looking at only the two tables involved, rather
+/// than building a whole new `Metrics`.
+///
+/// The evaluator doesn't own the plan. It must be given the same plan it
+/// was created with, and every swap must be made through `apply_swap`.
+/// After any other move, `refresh_tables` catches up with the tables it
+/// changed.
+#[derive(Debug, Clone)]
+pub(crate) struct IncrementalEvaluator<'a> {
+    problem: &'a Problem,
+    // Index by guest to get the table they sit at.
+    table_of: Vec<usize>,
+    // Index by guest to get how they feel about everyone else at the table,
+    // weighted by where they sit.
+    happinesses: Vec<i64>,
+    // Index by table to get the sum of the happinesses of the guests there.
+    table_happinesses: Vec<i64>,
+    total_happiness: i64,
+}
+
+impl<'a> IncrementalEvaluator<'a> {
+    pub fn new(problem: &'a Problem, plan: &[Vec<usize>]) -> Self {
+        let n_guests = problem.relations().len();
+        let mut evaluator = Self {
+            problem,
+            table_of: vec![0; n_guests],
+            happinesses: vec![0; n_guests],
+            table_happinesses: vec![0; plan.len()],
+            total_happiness: 0,
+        };
+
+        for (table_id, table) in plan.iter().enumerate() {
+            for guest in table {
+                evaluator.table_of[*guest] = table_id;
+            }
+            for (seat, guest) in table.iter().enumerate() {
+                evaluator.happinesses[*guest] = evaluator.happiness_at(plan, table_id, seat);
+            }
+            evaluator.table_happinesses[table_id] = evaluator.sum_table(plan, table_id);
+        }
+        evaluator.total_happiness = evaluator.table_happinesses.iter().sum();
+
+        evaluator
+    }
+
+    /// Returns the sum of everyone's happiness.
+    pub fn total_happiness(&self) -> i64 {
+        self.total_happiness
+    }
+
+    /// Returns how each guest feels about everyone else at their table.
+    pub fn happinesses(&self) -> &[i64] {
+        &self.happinesses
+    }
+
+    /// Returns the table the guest sits at.
+    pub fn table_of(&self, guest: usize) -> usize {
+        self.table_of[guest]
+    }
+
+    /// Returns how much total happiness would change if the swap were made.
+    /// Takes time proportional to the size of the two tables.
+    pub fn swap_delta(&self, plan: &Plan, swap: Swap) -> i64 {
+        let Swap {
+            table1,
+            seat1,
+            table2,
+            seat2,
+        } = swap;
+        let guest1 = plan[table1][seat1];
+        let guest2 = plan[table2][seat2];
+        if guest1 == guest2 {
+            return 0;
+        }
+
+        // Only the relationships between the two swapped guests and everyone
+        // else change. If the two are at the same table, the relationship
+        // between them stays in the same pair of seats and doesn't change.
+        // Relationships are symmetric, so each one counts twice.
+        let delta = self.seat_delta(plan, table1, seat1, guest1, guest2)
+            + self.seat_delta(plan, table2, seat2, guest2, guest1);
+        2 * delta
+    }
+
+    /// Makes the swap, and updates the evaluator to match.
+    /// Takes time proportional to the size of the two tables.
+    pub fn apply_swap(&mut self, plan: &mut Plan, swap: Swap) {
+        let Swap {
+            table1,
+            seat1,
+            table2,
+            seat2,
+        } = swap;
+        let guest1 = plan[table1][seat1];
+        let guest2 = plan[table2][seat2];
+        if guest1 == guest2 {
+            return;
+        }
+
+        // Everyone else at the two tables now sits next to a different guest.
+        let problem = self.problem;
+        for (table, seat, leaving, arriving) in [
+            (table1, seat1, guest1, guest2),
+            (table2, seat2, guest2, guest1),
+        ] {
+            for (other_seat, other) in plan[table].iter().enumerate() {
+                if *other == guest1 || *other == guest2 {
+                    continue;
+                }
+                let weight = problem.weight(table, seat, other_seat);
+                self.happinesses[*other] += weight
+                    * (problem.relations().relationship(*other, arriving)
+                        - problem.relations().relationship(*other, leaving));
+            }
+        }
+
+        plan[table1][seat1] = guest2;
+        plan[table2][seat2] = guest1;
+        self.table_of[guest1] = table2;
+        self.table_of[guest2] = table1;
+
+        // The swapped guests have a whole new set of neighbours.
+        self.happinesses[guest1] = self.happiness_at(plan, table2, seat2);
+        self.happinesses[guest2] = self.happiness_at(plan, table1, seat1);
+
+        // If both guests are at the same table, the second pass changes nothing.
+        for table in [table1, table2] {
+            let old = self.table_happinesses[table];
+            let new = self.sum_table(plan, table);
+            self.table_happinesses[table] = new;
+            self.total_happiness += new - old;
+        }
+    }
+
+    /// Catches up with a move already made to the plan, which only changed
+    /// who sits at the given tables, or where they sit there.
+    /// Takes time proportional to the square of the size of those tables.
+    pub fn refresh_tables(&mut self, plan: &[Vec<usize>], tables: &[usize]) {
+        for &table in tables {
+            for (seat, guest) in plan[table].iter().enumerate() {
+                self.table_of[*guest] = table;
+                self.happinesses[*guest] = self.happiness_at(plan, table, seat);
+            }
+            let old = self.table_happinesses[table];
+            let new = self.sum_table(plan, table);
+            self.table_happinesses[table] = new;
+            self.total_happiness += new - old;
+        }
+    }
+
+    // How much the relationships between the guest in the seat and everyone
+    // else at the table (apart from the two being swapped) change, if the
+    // guest in the seat is replaced.
+    fn seat_delta(
+        &self,
+        plan: &Plan,
+        table: usize,
+        seat: usize,
+        leaving: usize,
+        arriving: usize,
+    ) -> i64 {
+        let relations = self.problem.relations();
+        plan[table]
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| **other != leaving && **other != arriving)
+            .map(|(other_seat, other)| {
+                self.problem.weight(table, seat, other_seat)
+                    * (relations.relationship(arriving, *other)
+                        - relations.relationship(leaving, *other))
+            })
+            .sum()
+    }
+
+    // How happy the guest in the seat is with everyone else at the table.
+    fn happiness_at(&self, plan: &[Vec<usize>], table: usize, seat: usize) -> i64 {
+        let guest = plan[table][seat];
+        plan[table]
+            .iter()
+            .enumerate()
+            .filter(|(other_seat, _)| *other_seat != seat)
+            .map(|(other_seat, other)| {
+                self.problem.weight(table, seat, other_seat)
+                    * self.problem.relations().relationship(guest, *other)
+            })
+            .sum()
+    }
+
+    fn sum_table(&self, plan: &[Vec<usize>], table: usize) -> i64 {
+        plan[table]
+            .iter()
+            .map(|guest| self.happinesses[*guest])
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+
+    use crate::metrics::Metrics;
+    use crate::test_util::random_wedding;
+    use crate::{AdjacencyWeights, Layout, TableShape};
+
+    fn random_swap(rng: &mut impl Rng, plan: &Plan) -> Swap {
+        let table1 = rng.gen_range(0..plan.len());
+        let table2 = rng.gen_range(0..plan.len());
+        Swap {
+            table1,
+            seat1: rng.gen_range(0..plan[table1].len()),
+            table2,
+            seat2: rng.gen_range(0..plan[table2].len()),
+        }
+    }
+
+    // Makes random swaps, and moves guests into the empty seat, checking
+    // the evaluator against `Metrics` after each.
+    fn check_moves(rng: &mut impl Rng, problem: &Problem) {
+        let mut plan: Plan = (0..problem.n_tables)
+            .map(|table| {
+                (0..problem.relations().len())
+                    .filter(|guest| guest % problem.n_tables == table)
+                    .collect()
+            })
+            .collect();
+        let mut evaluator = IncrementalEvaluator::new(problem, &plan);
+
+        for i in 0..200 {
+            if i % 2 == 0 {
+                let swap = random_swap(rng, &plan);
+                let before = Metrics::new(&plan, problem).total_happiness();
+                let delta = evaluator.swap_delta(&plan, swap);
+                evaluator.apply_swap(&mut plan, swap);
+                assert_eq!(
+                    Metrics::new(&plan, problem).total_happiness(),
+                    before + delta
+                );
+            } else {
+                let to = (0..plan.len())
+                    .find(|table| plan[*table].len() < problem.capacity(*table))
+                    .unwrap();
+                let from = (to + rng.gen_range(1..plan.len())) % plan.len();
+                let seat = rng.gen_range(0..plan[from].len());
+                let guest = plan[from].remove(seat);
+                plan[to].push(guest);
+                evaluator.refresh_tables(&plan, &[from, to]);
+            }
+            let metrics = Metrics::new(&plan, problem);
+
+            assert_eq!(evaluator.total_happiness(), metrics.total_happiness());
+            assert!(evaluator
+                .happinesses
+                .iter()
+                .copied()
+                .eq(metrics.happinesses()));
+            for (table_id, table) in plan.iter().enumerate() {
+                for guest in table {
+                    assert_eq!(evaluator.table_of[*guest], table_id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn evaluator_matches_metrics() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let problem = random_wedding(&mut rng, 23, 4);
+        check_moves(&mut rng, &problem);
+    }
+
+    #[test]
+    fn evaluator_matches_metrics_with_layout() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let weights = AdjacencyWeights {
+            neighbour: 3,
+            across: 2,
+            same_table: 1,
+        };
+        for shape in [
+            TableShape::Round,
+            TableShape::Rectangular,
+            TableShape::Banquet,
+        ] {
+            let problem = random_wedding(&mut rng, 23, 4)
+                .with_layout(Layout {
+                    shapes: vec![shape],
+                    weights,
+                })
+                .unwrap();
+            check_moves(&mut rng, &problem);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use crate::hill_climb::{
+    check_feasible, check_groups, fitness, make_move, random_plan, Neighbourhood,
+};
+use crate::metrics::Metrics;
+use crate::objective::{Objective, TotalHappiness};
+use crate::{
+    ClimbMode, HillClimbingPlanner, LocalSearch, Plan, PlanError, Problem, SeatingPlanner,
+};
+
+use std::cmp::Reverse;
+use std::num::NonZeroUsize;
+
+use rand::prelude::*;
+
+type Float = ordered_float::NotNan<f64>;
+
+// How many plans are in each generation, unless told otherwise.
+const DEFAULT_POPULATION_SIZE: usize = 50;
+
+// How many generations without a better plan before we give up,
+// unless told otherwise.
+const DEFAULT_PATIENCE: usize = 50;
+
+// How likely a child is to have a random move made to it, unless told otherwise.
+const DEFAULT_MUTATION_RATE: f64 = 0.3;
+
+// How many plans compete to be each parent.
+const TOURNAMENT_SIZE: usize = 3;
+
+// How many of the best plans survive into the next generation unchanged.
+const N_ELITES: usize = 2;
+
+// The termination threshold for the hill climber in memetic mode. This is
+// looser than for a standalone hill climber, because it runs on every child.
+// SAFETY: the argument to `new_unchecked` must not be NaN.
+// The value is constant, so we can see it is not NaN.
+const LOCAL_SEARCH_THRESHOLD: Float = unsafe { Float::new_unchecked(0.01) };
+
+/// A genetic algorithm over seating plans. Children are built from whole
+/// tables of both parents, so guests who sit well together stay together.
+/// With local search, every child is also hill-climbed, which makes it a
+/// memetic algorithm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneticPlanner<R, O = TotalHappiness> {
+    rng: R,
+    objective: O,
+    population_size: NonZeroUsize,
+    // How many generations without a new best plan before we stop.
+    patience: usize,
+    // The probability a child has a random move made to it.
+    mutation_rate: f64,
+    // How to hill climb each child, if at all.
+    local_search: Option<ClimbMode>,
+}
+
+impl<R> GeneticPlanner<R>
+where
+    R: Rng,
+{
+    pub fn new(rng: R) -> Self {
+        Self::with_objective(rng, TotalHappiness)
+    }
+}
+
+impl<R, O> GeneticPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    pub fn with_objective(rng: R, objective: O) -> Self {
+        Self {
+            rng,
+            objective,
+            population_size: NonZeroUsize::new(DEFAULT_POPULATION_SIZE).unwrap(),
+            patience: DEFAULT_PATIENCE,
+            mutation_rate: DEFAULT_MUTATION_RATE,
+            local_search: None,
+        }
+    }
+
+    pub fn with_population_size(self, population_size: NonZeroUsize) -> Self {
+        Self {
+            population_size,
+            ..self
+        }
+    }
+
+    /// Sets how many generations without finding a better plan we allow
+    /// before stopping.
+    pub fn with_patience(self, patience: usize) -> Self {
+        Self { patience, ..self }
+    }
+
+    /// Sets the probability that a child has a random move made to it.
+    /// # Panics
+    /// Panics if the rate isn't between 0 and 1.
+    pub fn with_mutation_rate(self, mutation_rate: f64) -> Self {
+        assert!((0.0..=1.0).contains(&mutation_rate));
+        Self {
+            mutation_rate,
+            ..self
+        }
+    }
+
+    /// Hill climbs every child before it joins the population.
+    pub fn with_local_search(self, mode: ClimbMode) -> Self {
+        Self {
+            local_search: Some(mode),
+            ..self
+        }
+    }
+
+    // Picks the best of a few random plans.
+    fn tournament<'p, F: Ord>(&mut self, population: &'p [(Plan, F)]) -> &'p (Plan, F) {
+        (0..TOURNAMENT_SIZE)
+            .map(|_| {
+                population
+                    .choose(&mut self.rng)
+                    .expect("Population is not empty.")
+            })
+            .max_by(|(_, f1), (_, f2)| f1.cmp(f2))
+            .expect("Tournament size is not zero.")
+    }
+}
+
+impl<R, O> SeatingPlanner for GeneticPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
+}
+
+impl<R, O> LocalSearch for GeneticPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    // The plan joins a population that is otherwise random.
+    fn improve(&mut self, problem: &Problem, plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        let neighbourhood = Neighbourhood::new(problem);
+
+        let mut population = Vec::with_capacity(self.population_size.get());
+        let plan_fitness = fitness(&self.objective, &Metrics::new(&plan, problem));
+        population.push((plan, plan_fitness));
+        for _ in 1..self.population_size.get() {
+            let plan = random_plan(&mut self.rng, problem)?;
+            let plan_fitness = fitness(&self.objective, &Metrics::new(&plan, problem));
+            population.push((plan, plan_fitness));
+        }
+
+        population.sort_by_key(|(_, f)| Reverse(*f));
+        let (mut best, mut best_fitness) = population[0].clone();
+
+        let mut n_stale = 0;
+        while n_stale < self.patience {
+            n_stale += 1;
+
+            let n_elites = N_ELITES.min(population.len());
+            let mut next_generation = population[..n_elites].to_vec();
+
+            while next_generation.len() < self.population_size.get() {
+                let (parent1, fitness1) = self.tournament(&population);
+                let (parent2, fitness2) = self.tournament(&population);
+                let mut child = crossover(&mut self.rng, &neighbourhood, parent1, parent2)
+                    .unwrap_or_else(|| {
+                        // The repair couldn't fit everyone in, so take after
+                        // the fitter parent.
+                        if fitness1 >= fitness2 {
+                            parent1.clone()
+                        } else {
+                            parent2.clone()
+                        }
+                    });
+
+                if self.rng.gen_bool(self.mutation_rate) {
+                    if let Some(change) = neighbourhood.random_move(&mut self.rng, &child) {
+                        make_move(&mut child, &change);
+                    }
+                }
+
+                if let Some(mode) = self.local_search {
+                    HillClimbingPlanner::with_objective(
+                        &mut self.rng,
+                        LOCAL_SEARCH_THRESHOLD,
+                        &self.objective,
+                    )
+                    .with_mode(mode)
+                    .climb(&neighbourhood, &mut child);
+                }
+
+                let child_fitness = fitness(&self.objective, &Metrics::new(&child, problem));
+                if child_fitness > best_fitness {
+                    best.clone_from(&child);
+                    best_fitness = child_fitness;
+                    n_stale = 0;
+                }
+                next_generation.push((child, child_fitness));
+            }
+
+            population = next_generation;
+            population.sort_by_key(|(_, f)| Reverse(*f));
+        }
+
+        check_feasible(best, problem)
+    }
+}
+
+// Builds a child by taking turns to pick the table from each parent with the
+// most guests who haven't been seated yet. Tables keep their number, so
+// capacities and pins still hold. Anyone left over is then seated wherever
+// they add the most happiness.
+// Returns `None` if the leftover guests don't fit.
+fn crossover<R>(
+    mut rng: R,
+    neighbourhood: &Neighbourhood,
+    parent1: &Plan,
+    parent2: &Plan,
+) -> Option<Plan>
+where
+    R: Rng,
+{
+    let problem = neighbourhood.problem;
+    let n_tables = problem.n_tables;
+    let mut child = vec![Vec::new(); n_tables];
+    let mut filled = vec![false; n_tables];
+    let mut seated = vec![false; problem.relations().len()];
+
+    let parents = [parent1, parent2];
+    let mut turn = rng.gen_range(0..parents.len());
+    for _ in 0..n_tables {
+        let parent = parents[turn];
+        turn = 1 - turn;
+
+        let n_unseated = |table: usize| parent[table].iter().filter(|g| !seated[**g]).count();
+        let table = (0..n_tables)
+            .filter(|t| !filled[*t])
+            .max_by_key(|t| n_unseated(*t))
+            .expect("There is an unfilled table each turn.");
+        filled[table] = true;
+
+        // Only take whole groups, so nobody is split from their group.
+        for guest in &parent[table] {
+            let unit = neighbourhood.group(*guest);
+            let all_here = unit.iter().all(|g| parent[table].contains(g));
+            if !seated[*guest] && all_here && unit.iter().all(|g| !seated[*g]) {
+                for g in unit {
+                    seated[*g] = true;
+                    child[table].push(*g);
+                }
+            }
+        }
+    }
+
+    // Seat the biggest leftover groups first, while there is still room.
+    let mut leftovers: Vec<&[usize]> = (0..seated.len())
+        .filter(|g| !seated[*g])
+        .map(|g| neighbourhood.group(g))
+        .collect();
+    leftovers.sort_unstable();
+    leftovers.dedup();
+    leftovers.shuffle(&mut rng);
+    leftovers.sort_by_key(|unit| Reverse(unit.len()));
+
+    for unit in leftovers {
+        let gain = |table: usize| -> i64 {
+            unit.iter()
+                .flat_map(|g| child[table].iter().map(move |other| (*g, *other)))
+                .map(|(g, other)| problem.relations().relationship(g, other))
+                .sum()
+        };
+        // Fill tables that are below their minimum occupancy first.
+        let table = (0..n_tables)
+            .filter(|t| child[*t].len() + unit.len() <= problem.capacity(*t))
+            .filter(|t| unit.iter().all(|g| problem.allows(*g, *t)))
+            .max_by_key(|t| (child[*t].len() < problem.min_occupancy, gain(*t)))?;
+        child[table].extend_from_slice(unit);
+    }
+
+    if child
+        .iter()
+        .all(|table| table.len() >= problem.min_occupancy)
+    {
+        Some(child)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hill_climb::undo_move;
+    use crate::test_util::{
+        assert_feasible, assert_improves_on_start, assert_partition, constrained_wedding,
+        random_wedding,
+    };
+
+    #[test]
+    fn crossover_keeps_partition() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let problem = Problem::with_capacities(
+            random_wedding(&mut rng, 25, 4).relations().clone(),
+            vec![8, 7, 6, 5],
+        )
+        .unwrap()
+        .with_constraints(vec![vec![0, 1, 2], vec![3, 4]], vec![])
+        .unwrap()
+        .with_pins(vec![(5, 3)], vec![])
+        .unwrap();
+        let neighbourhood = Neighbourhood::new(&problem);
+
+        let mut n_children = 0;
+        for _ in 0..100 {
+            let parent1 = random_plan(&mut rng, &problem).unwrap();
+            let parent2 = random_plan(&mut rng, &problem).unwrap();
+            // There's no child when the leftover groups don't fit in the
+            // seats that are left.
+            let child = match crossover(&mut rng, &neighbourhood, &parent1, &parent2) {
+                Some(child) => child,
+                None => continue,
+            };
+            n_children += 1;
+
+            assert_partition(&child, 25);
+            for (table, guests) in child.iter().enumerate() {
+                assert!(guests.len() <= problem.capacity(table));
+            }
+            let metrics = Metrics::new(&child, &problem);
+            assert_eq!(metrics.together_violations(), 0);
+            assert_eq!(metrics.pin_violations(), 0);
+        }
+        // The rng is seeded, so this doesn't change from run to run.
+        assert!(n_children > 50, "only {} children", n_children);
+    }
+
+    #[test]
+    fn genetic_never_worse_than_start() {
+        // The first plan in the population is the one the test starts from.
+        assert_improves_on_start(0, 20, 4, |rng| {
+            GeneticPlanner::new(rng)
+                .with_population_size(NonZeroUsize::new(10).unwrap())
+                .with_patience(5)
+        });
+    }
+
+    #[test]
+    fn genetic_respects_constraints() {
+        let problem = constrained_wedding(0);
+        let mut planner = GeneticPlanner::new(StdRng::seed_from_u64(0))
+            .with_population_size(NonZeroUsize::new(10).unwrap())
+            .with_patience(5);
+        let plan = planner.plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+    }
+
+    #[test]
+    fn memetic_finds_local_optimum() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let problem = random_wedding(&mut rng, 12, 3);
+        let mut planner = GeneticPlanner::new(&mut rng)
+            .with_population_size(NonZeroUsize::new(4).unwrap())
+            .with_patience(2)
+            .with_local_search(ClimbMode::SteepestAscent);
+        let mut plan = planner.plan(&problem).unwrap();
+        let total = Metrics::new(&plan, &problem).total_happiness();
+
+        for change in Neighbourhood::new(&problem).all_moves(&mut rng, &plan) {
+            make_move(&mut plan, &change);
+            assert!(Metrics::new(&plan, &problem).total_happiness() <= total);
+            undo_move(&mut plan, &change);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::hill_climb::check_feasible;
+use crate::{Plan, PlanError, Problem, SeatingPlanner};
+
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// How a `GreedyPlanner` chooses who to seat next.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub enum Insertion {
+    /// Fill one table at a time, each time seating whoever adds the most
+    /// happiness to it.
+    #[default]
+    BestGain,
+    /// Fill all the tables at once, each time seating whoever would lose
+    /// the most by not getting their best table.
+    Regret,
+}
+
+impl FromStr for Insertion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "best" | "best-gain" | "greedy" => Ok(Insertion::BestGain),
+            "regret" => Ok(Insertion::Regret),
+            _ => Err(anyhow!("Unrecognised insertion strategy")),
+        }
+    }
+}
+
+/// Builds a plan deterministically, by seeding each table with the most
+/// connected guest left and filling the seats around them.
+/// It's quick, so it makes a good baseline and a good starting point
+/// for local search.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct GreedyPlanner {
+    insertion: Insertion,
+}
+
+impl GreedyPlanner {
+    pub fn new(insertion: Insertion) -> Self {
+        Self { insertion }
+    }
+}
+
+impl SeatingPlanner for GreedyPlanner {
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = self.construct(problem)?;
+        check_feasible(plan, problem)
+    }
+
+    // Greedy choices can leave someone without a friend, or next to
+    // someone they must be kept apart from, which a local search can
+    // often repair.
+    fn construct(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let mut seating = Seating::new(problem);
+
+        // Pinned guests (and anyone who must sit with them) go first.
+        for unit in 0..seating.units.len() {
+            let pinned = seating.units[unit]
+                .iter()
+                .find_map(|guest| problem.pinned_table(*guest));
+            if let Some(table) = pinned {
+                seating.seat(unit, table);
+            }
+        }
+
+        match self.insertion {
+            Insertion::BestGain => {
+                for table in 0..problem.n_tables {
+                    seating.seed(table);
+                    while let Some(unit) = seating.best_unit_for(table) {
+                        seating.seat(unit, table);
+                    }
+                }
+            }
+            Insertion::Regret => {
+                for table in 0..problem.n_tables {
+                    seating.seed(table);
+                }
+                while let Some((unit, table)) = seating.most_regretted() {
+                    seating.seat(unit, table);
+                }
+            }
+        }
+
+        seating.seat_leftovers()?;
+        Ok(seating.plan)
+    }
+}
+
+// A plan that is being built.
+struct Seating<'a> {
+    problem: &'a Problem,
+    units: Vec<Vec<usize>>,
+    seated: Vec<bool>,
+    // Index by guest to get the table they're seated at, if any.
+    table_of: Vec<Option<usize>>,
+    // Index by guest to get the guests they must be kept apart from.
+    apart_from: Vec<Vec<usize>>,
+    // How many guests we aim to seat at each table, so that the empty seats
+    // are spread out and every table reaches its minimum occupancy.
+    targets: Vec<usize>,
+    plan: Plan,
+}
+
+impl<'a> Seating<'a> {
+    fn new(problem: &'a Problem) -> Self {
+        let n_guests = problem.relations().len();
+        let units = problem.units();
+
+        let mut apart_from = vec![Vec::new(); n_guests];
+        for &(i, j) in problem.apart() {
+            apart_from[i].push(j);
+            apart_from[j].push(i);
+        }
+
+        // Take empty seats from the biggest tables first.
+        let mut targets: Vec<usize> = problem.capacities().collect();
+        for _ in n_guests..problem.n_seats() {
+            let biggest = (0..targets.len())
+                .min_by_key(|t| Reverse(targets[*t]))
+                .expect("There is at least one table.");
+            targets[biggest] -= 1;
+        }
+
+        Self {
+            problem,
+            seated: vec![false; units.len()],
+            units,
+            table_of: vec![None; n_guests],
+            apart_from,
+            targets,
+            plan: vec![Vec::new(); problem.n_tables],
+        }
+    }
+
+    fn seat(&mut self, unit: usize, table: usize) {
+        self.seated[unit] = true;
+        for guest in &self.units[unit] {
+            self.table_of[*guest] = Some(table);
+            self.plan[table].push(*guest);
+        }
+    }
+
+    fn unseated(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.units.len()).filter(move |unit| !self.seated[*unit])
+    }
+
+    // Whether the unit can join the table without going over its target,
+    // sitting somewhere they're not allowed, or sitting with someone
+    // they must be kept apart from.
+    fn fits(&self, unit: usize, table: usize) -> bool {
+        let unit = &self.units[unit];
+        self.plan[table].len() + unit.len() <= self.targets[table]
+            && unit.iter().all(|guest| self.problem.allows(*guest, table))
+            && !self.has_conflict(unit, table)
+    }
+
+    fn has_conflict(&self, unit: &[usize], table: usize) -> bool {
+        unit.iter()
+            .flat_map(|guest| &self.apart_from[*guest])
+            .any(|other| self.table_of[*other] == Some(table))
+    }
+
+    // How much happiness the unit would add to the table, counting both
+    // directions of every relationship.
+    fn gain(&self, unit: usize, table: usize) -> i64 {
+        let relations = self.problem.relations();
+        let gain: i64 = self.units[unit]
+            .iter()
+            .flat_map(|guest| self.plan[table].iter().map(move |other| (guest, other)))
+            .map(|(guest, other)| relations.relationship(*guest, *other))
+            .sum();
+        2 * gain
+    }
+
+    // How much the unit likes everyone who hasn't been seated yet.
+    fn connectedness(&self, unit: usize) -> i64 {
+        let relations = self.problem.relations();
+        self.units[unit]
+            .iter()
+            .flat_map(|guest| (0..relations.len()).map(move |other| (guest, other)))
+            .filter(|(_, other)| self.table_of[*other].is_none())
+            .map(|(guest, other)| relations.relationship(*guest, other).max(0))
+            .sum()
+    }
+
+    // Starts an empty table with the most connected unit that fits.
+    fn seed(&mut self, table: usize) {
+        if !self.plan[table].is_empty() {
+            return;
+        }
+        let seed = self
+            .unseated()
+            .filter(|unit| self.fits(*unit, table))
+            .min_by_key(|unit| Reverse(self.connectedness(*unit)));
+        if let Some(unit) = seed {
+            self.seat(unit, table);
+        }
+    }
+
+    // The unit who would add the most happiness to the table.
+    // Ties go to the lowest-numbered unit, so the plan is deterministic.
+    fn best_unit_for(&self, table: usize) -> Option<usize> {
+        self.unseated()
+            .filter(|unit| self.fits(*unit, table))
+            .min_by_key(|unit| Reverse(self.gain(*unit, table)))
+    }
+
+    // The unit with the biggest difference between their best and second
+    // best tables, along with their best table. A unit with only one table
+    // left is always the most urgent.
+    fn most_regretted(&self) -> Option<(usize, usize)> {
+        self.unseated()
+            .filter_map(|unit| {
+                let mut gains: Vec<(i64, usize)> = (0..self.plan.len())
+                    .filter(|t| self.fits(unit, *t))
+                    .map(|t| (self.gain(unit, t), t))
+                    .collect();
+                gains.sort_by_key(|(gain, t)| (Reverse(*gain), *t));
+
+                let (best_gain, best_table) = *gains.first()?;
+                let regret = gains
+                    .get(1)
+                    .map_or(i64::MAX, |(second_gain, _)| best_gain - second_gain);
+                Some(((regret, best_gain), unit, best_table))
+            })
+            .min_by_key(|(key, _, _)| Reverse(*key))
+            .map(|(_, unit, table)| (unit, table))
+    }
+
+    // Seats anyone who didn't fit around the target sizes wherever there's
+    // room, avoiding the people they must be kept apart from if possible.
+    fn seat_leftovers(&mut self) -> Result<(), PlanError> {
+        let mut leftovers: Vec<usize> = self.unseated().collect();
+        leftovers.sort_by_key(|unit| Reverse(self.units[*unit].len()));
+
+        for unit in leftovers {
+            let table = (0..self.plan.len())
+                .filter(|t| {
+                    self.plan[*t].len() + self.units[unit].len() <= self.problem.capacity(*t)
+                })
+                .filter(|t| self.units[unit].iter().all(|g| self.problem.allows(*g, *t)))
+                .min_by_key(|t| {
+                    let conflict = self.has_conflict(&self.units[unit], *t);
+                    (conflict, Reverse(self.gain(unit, *t)))
+                })
+                .ok_or(PlanError::Unpackable)?;
+            self.seat(unit, table);
+        }
+
+        if self
+            .plan
+            .iter()
+            .all(|table| table.len() >= self.problem.min_occupancy)
+        {
+            Ok(())
+        } else {
+            Err(PlanError::Unpackable)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+
+    use crate::metrics::Metrics;
+    use crate::test_util::{assert_feasible, random_wedding};
+    use crate::{ClimbMode, GuestRelations, HillClimbingPlanner, WarmStart};
+
+    type Float = ordered_float::NotNan<f64>;
+
+    // Four cliques of three, who dislike everyone else.
+    fn cliques() -> Problem {
+        let matrix = (0..12)
+            .map(|i| {
+                (0..12)
+                    .map(|j| match (i == j, i / 3 == j / 3) {
+                        (true, _) => 0,
+                        (false, true) => 5,
+                        (false, false) => -1,
+                    })
+                    .collect()
+            })
+            .collect();
+        Problem::new(GuestRelations::new(matrix).unwrap(), 4).unwrap()
+    }
+
+    #[test]
+    fn greedy_finds_cliques() {
+        for insertion in [Insertion::BestGain, Insertion::Regret] {
+            let plan = GreedyPlanner::new(insertion).plan(&cliques()).unwrap();
+            assert_eq!(
+                Metrics::new(&plan, &cliques()).total_happiness(),
+                4 * 3 * 2 * 5
+            );
+        }
+    }
+
+    #[test]
+    fn greedy_is_deterministic_and_valid() {
+        let problem = Problem::with_capacities(cliques().relations().clone(), vec![5, 4, 4])
+            .unwrap()
+            .with_min_occupancy(3)
+            .unwrap()
+            .with_constraints(vec![vec![0, 4]], vec![(1, 2)])
+            .unwrap()
+            .with_pins(vec![(7, 2)], vec![(8, 2)])
+            .unwrap();
+
+        for insertion in [Insertion::BestGain, Insertion::Regret] {
+            let plan = GreedyPlanner::new(insertion).plan(&problem).unwrap();
+            assert_eq!(GreedyPlanner::new(insertion).plan(&problem).unwrap(), plan);
+
+            assert_feasible(&plan, &problem);
+        }
+    }
+
+    #[test]
+    fn warm_start_from_greedy() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let problem = random_wedding(&mut rng, 20, 4);
+
+        let greedy = GreedyPlanner::new(Insertion::Regret)
+            .plan(&problem)
+            .unwrap();
+        let greedy_total = Metrics::new(&greedy, &problem).total_happiness();
+
+        let mut planner = WarmStart {
+            start: GreedyPlanner::new(Insertion::Regret),
+            search: HillClimbingPlanner::new(rng, Float::new(0.01).unwrap())
+                .with_mode(ClimbMode::SteepestAscent),
+        };
+        let plan = planner.plan(&problem).unwrap();
+        assert!(Metrics::new(&plan, &problem).total_happiness() >= greedy_total);
+    }
+
+    #[test]
+    fn warm_start_repairs_greedy_plan() {
+        // Greedy leaves somebody here without a friend.
+        let problem = random_wedding(&mut StdRng::seed_from_u64(4), 12, 3).with_friend_required();
+        assert!(matches!(
+            GreedyPlanner::default().plan(&problem),
+            Err(PlanError::Infeasible { .. })
+        ));
+
+        let mut planner = WarmStart {
+            start: GreedyPlanner::default(),
+            search: HillClimbingPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap()),
+        };
+        let plan = planner.plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+    }
+}
@@ -1,35 +1,254 @@
+use crate::evaluator::IncrementalEvaluator;
 use crate::metrics::Metrics;
-use crate::{Plan, Problem, SeatingPlanner};
+use crate::moves::SeatSampler;
+use crate::objective::{Objective, TotalHappiness};
+use crate::{GuestSampling, LocalSearch, MoveMix, Plan, PlanError, Problem, SeatingPlanner};
 
-use std::{collections::VecDeque, num::NonZeroUsize};
+use std::{cell::RefCell, cmp::Reverse, num::NonZeroUsize, str::FromStr};
 
+use anyhow::anyhow;
 use rand::prelude::*;
 
 type Float = ordered_float::NotNan<f64>;
 
 // The ratio for exponential-moving-average, which
 // is used to terminate the hill-climbing algorithms.
-// SAFETY: the argument to `unchecked_new` must not be NaN.
+// SAFETY: the argument to `new_unchecked` must not be NaN.
 // The value is constant, so we can see it is not NaN.
 // We use the unsafe version because `Float::new` is not `const`.
-const EMA_FACTOR: Float = unsafe { Float::unchecked_new(0.01) };
+const EMA_FACTOR: Float = unsafe { Float::new_unchecked(0.01) };
 
 fn shift_ema(old_ema: Float, new_val: Float) -> Float {
     (EMA_FACTOR * new_val) + ((Float::new(1.0).unwrap() - EMA_FACTOR) * old_ema)
 }
 
+// How many times we try to build a starting plan before giving up.
+const MAX_PACKING_ATTEMPTS: usize = 100;
+
+// How many random moves we propose before deciding that nobody can move.
+const MAX_PROPOSALS: usize = 100;
+
+// Plans that break fewer hard constraints are always better,
+// then we look at the objective.
+pub(crate) fn fitness<O>(objective: &O, metrics: &Metrics) -> (Reverse<usize>, O::Score)
+where
+    O: Objective,
+{
+    (Reverse(metrics.n_violations()), objective.evaluate(metrics))
+}
+
+pub(crate) fn check_feasible(plan: Plan, problem: &Problem) -> Result<Plan, PlanError> {
+    let n_violations = Metrics::new(&plan, problem).n_violations();
+    if n_violations == 0 {
+        Ok(plan)
+    } else {
+        Err(PlanError::Infeasible { n_violations })
+    }
+}
+
+// Checks that a plan to improve on keeps every group together.
+pub(crate) fn check_groups(plan: &[Vec<usize>], problem: &Problem) -> Result<(), PlanError> {
+    let mut table_of = vec![0; problem.relations().len()];
+    for (table, guests) in plan.iter().enumerate() {
+        for &guest in guests {
+            table_of[guest] = table;
+        }
+    }
+    let split = problem.together().iter().any(|group| {
+        group
+            .iter()
+            .any(|&guest| table_of[guest] != table_of[group[0]])
+    });
+    if split {
+        Err(PlanError::SplitGroup)
+    } else {
+        Ok(())
+    }
+}
+
+// Scores the moves proposed by the climbers, annealing and tabu search.
+// Swaps are scored incrementally when the objective only depends on total
+// happiness and a swap can't break any hard constraints. Anything else is
+// scored by making the move and building a whole new `Metrics`. Kept moves
+// are passed on to the neighbourhood, so it can keep up with the plan.
+pub(crate) struct MoveScorer<'a, 'p, O> {
+    neighbourhood: &'a Neighbourhood<'p>,
+    objective: &'a O,
+    evaluator: Option<IncrementalEvaluator<'p>>,
+    // Only groups can be broken up, and they always move together.
+    n_violations: usize,
+    // Whether the last proposed move was made to the plan to score it.
+    made: bool,
+}
+
+impl<'a, 'p, O> MoveScorer<'a, 'p, O>
+where
+    O: Objective,
+{
+    pub(crate) fn new(neighbourhood: &'a Neighbourhood<'p>, objective: &'a O, plan: &Plan) -> Self {
+        let problem = neighbourhood.problem;
+        neighbourhood.forget_plan();
+        let incremental = objective.evaluate_total(0).is_some()
+            && problem.apart().is_empty()
+            && problem.pins().is_empty()
+            && problem.forbidden().is_empty()
+            && !problem.require_friend;
+        Self {
+            neighbourhood,
+            objective,
+            evaluator: incremental.then(|| IncrementalEvaluator::new(problem, plan)),
+            n_violations: Metrics::new(plan, problem).n_violations(),
+            made: false,
+        }
+    }
+
+    pub(crate) fn fitness(&self, plan: &Plan) -> (Reverse<usize>, O::Score) {
+        let problem = self.neighbourhood.problem;
+        fitness(self.objective, &Metrics::new(plan, problem))
+    }
+
+    // Scores the plan as it would be after the move, which must then be
+    // either kept or rejected.
+    pub(crate) fn propose(&mut self, plan: &mut Plan, change: &Move) -> (Reverse<usize>, O::Score) {
+        if let (Some(evaluator), Move::Swap(swap)) = (&self.evaluator, change) {
+            let total = evaluator.total_happiness() + evaluator.swap_delta(plan, *swap);
+            if let Some(score) = self.objective.evaluate_total(total) {
+                self.made = false;
+                return (Reverse(self.n_violations), score);
+            }
+        }
+        make_move(plan, change);
+        self.made = true;
+        self.fitness(plan)
+    }
+
+    pub(crate) fn keep(&mut self, plan: &mut Plan, change: &Move) {
+        match (&mut self.evaluator, change) {
+            (Some(evaluator), Move::Swap(swap)) if !self.made => evaluator.apply_swap(plan, *swap),
+            (Some(evaluator), _) => evaluator.refresh_tables(plan, &touched_tables(change)),
+            (None, _) => {}
+        }
+        self.neighbourhood.kept(plan, change);
+    }
+
+    pub(crate) fn reject(&mut self, plan: &mut Plan, change: &Move) {
+        if self.made {
+            undo_move(plan, change);
+        }
+    }
+}
+
+// How often we try moving someone into an empty seat, rather than swapping
+// two guests, when there are empty seats to move into.
+const RELOCATION_PROBABILITY: f64 = 0.5;
+
+// How often we try swapping two guests at the same table, when it
+// matters where people sit at their table.
+const SEAT_SWAP_PROBABILITY: f64 = 0.25;
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub(crate) struct Swap {
+    pub(crate) table1: usize,
+    pub(crate) seat1: usize,
+    pub(crate) table2: usize,
+    pub(crate) seat2: usize,
+}
+
+// Moves a guest into an empty seat at another table.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
-struct Swap {
+pub(crate) struct Relocation {
+    from_table: usize,
+    seat: usize,
+    to_table: usize,
+}
+
+// Exchanges a group who must sit together with the same number of
+// guests from another table, so the group is never split up.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub(crate) struct GroupSwap {
     table1: usize,
-    seat1: usize,
+    seats1: Vec<usize>,
     table2: usize,
-    seat2: usize,
+    seats2: Vec<usize>,
 }
 
+// Moves a whole group into empty seats at another table.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub struct HillClimbingPlanner<R> {
+pub(crate) struct GroupRelocation {
+    from_table: usize,
+    // In ascending order.
+    seats: Vec<usize>,
+    to_table: usize,
+}
+
+// Moves the guest in each seat into the next seat. The guest in the last
+// seat moves into the first, or to the end of `end_table` if there is one,
+// leaving the first seat empty. Every seat is at a different table.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub(crate) struct Chain {
+    pub(crate) seats: Vec<(usize, usize)>,
+    pub(crate) end_table: Option<usize>,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub(crate) enum Move {
+    Swap(Swap),
+    Relocate(Relocation),
+    SwapGroup(GroupSwap),
+    RelocateGroup(GroupRelocation),
+    Chain(Chain),
+}
+
+// What we need to know about a problem to propose moves quickly.
+pub(crate) struct Neighbourhood<'a> {
+    pub(crate) problem: &'a Problem,
+    // Index by guest to find the group they must move with.
+    unit_of: Vec<usize>,
+    units: Vec<Vec<usize>>,
+    pinned: Vec<bool>,
+    moves: MoveMix,
+    sampling: GuestSampling,
+    // Worked out for the plan the last `MoveScorer` was made for, and
+    // kept up to date with the moves it keeps.
+    sampler: RefCell<Option<SeatSampler<'a>>>,
+}
+
+/// How a `HillClimbingPlanner` chooses which improving move to make.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub enum ClimbMode {
+    /// Try random moves, keeping the ones that help, until improvements
+    /// become rarer than the termination threshold.
+    #[default]
+    Stochastic,
+    /// Look through every move in a random order and make the first one
+    /// that helps. Stops when no move helps.
+    FirstImprovement,
+    /// Look through every move and make the one that helps the most.
+    /// Stops when no move helps.
+    SteepestAscent,
+}
+
+impl FromStr for ClimbMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stochastic" | "random" => Ok(ClimbMode::Stochastic),
+            "first" | "first-improvement" => Ok(ClimbMode::FirstImprovement),
+            "steepest" | "steepest-ascent" => Ok(ClimbMode::SteepestAscent),
+            _ => Err(anyhow!("Unrecognised hill climbing mode")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct HillClimbingPlanner<R, O = TotalHappiness> {
     rng: R,
     termination_threshold: Float,
+    objective: O,
+    mode: ClimbMode,
+    moves: MoveMix,
+    sampling: GuestSampling,
 }
 
 impl<R> HillClimbingPlanner<R>
@@ -37,65 +256,174 @@ where
     R: Rng,
 {
     pub fn new(rng: R, termination_threshold: Float) -> Self {
-        Self {
-            rng,
-            termination_threshold,
-        }
+        Self::with_objective(rng, termination_threshold, TotalHappiness)
     }
 }
 
-impl<R> SeatingPlanner for HillClimbingPlanner<R>
+impl<R, O> HillClimbingPlanner<R, O>
 where
     R: Rng,
+    O: Objective,
 {
-    fn plan(&mut self, problem: &Problem) -> Plan {
-        let relationships = &problem.relations;
-        let n_tables = problem.n_tables;
-        let table_size = relationships.len() / n_tables;
+    pub fn with_objective(rng: R, termination_threshold: Float, objective: O) -> Self {
+        Self {
+            rng,
+            termination_threshold,
+            objective,
+            mode: ClimbMode::default(),
+            moves: MoveMix::default(),
+            sampling: GuestSampling::default(),
+        }
+    }
 
-        let mut plan = random_plan(&mut self.rng, relationships.len(), n_tables);
+    /// Chooses how improving moves are found. The termination threshold
+    /// is only used by `ClimbMode::Stochastic`.
+    pub fn with_mode(self, mode: ClimbMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    /// Chooses which kinds of random move are tried. Only used by
+    /// `ClimbMode::Stochastic`, as the other modes look at every swap
+    /// and relocation.
+    pub fn with_moves(self, moves: MoveMix) -> Self {
+        Self { moves, ..self }
+    }
+
+    /// Chooses how the guests to move are picked. Only used by
+    /// `ClimbMode::Stochastic`.
+    pub fn with_sampling(self, sampling: GuestSampling) -> Self {
+        Self { sampling, ..self }
+    }
+
+    // Improves the plan in place.
+    pub(crate) fn climb(&mut self, neighbourhood: &Neighbourhood, plan: &mut Plan) {
+        match self.mode {
+            ClimbMode::Stochastic => self.climb_stochastic(neighbourhood, plan),
+            ClimbMode::FirstImprovement | ClimbMode::SteepestAscent => {
+                self.climb_exhaustive(neighbourhood, plan)
+            }
+        }
+    }
+
+    fn climb_stochastic(&mut self, neighbourhood: &Neighbourhood, plan: &mut Plan) {
+        let mut scorer = MoveScorer::new(neighbourhood, &self.objective, plan);
+        let mut current_fitness = scorer.fitness(plan);
 
         // A moving average of how often we update our best solution.
         let mut update_ema = Float::new(1.0).unwrap();
 
         while update_ema >= self.termination_threshold {
             // Propose a small random change.
+            let change = match neighbourhood.random_move(&mut self.rng, plan) {
+                Some(change) => change,
+                None => {
+                    // Everyone we tried to move is pinned in place.
+                    update_ema = shift_ema(update_ema, Float::new(0.0).unwrap());
+                    continue;
+                }
+            };
 
-            // TODO: if we use a priority queue (or similar) for the tables, we
-            // can increase the likelihood that the most miserable person will
-            // be moved.
-            let swap = get_random_swap(&mut self.rng, n_tables, table_size);
-
-            // Measure current utility.
-            let old_metrics = Metrics::new(&plan, relationships);
-
-            // Make the change and measure new utility.
-            let new_metrics = Metrics::new(&plan, relationships);
+            // Measure the utility of the changed plan.
+            let new_fitness = scorer.propose(plan, &change);
 
             // Check if we made things better or worse.
-            let updated: Float;
-            if new_metrics.total_happiness() > old_metrics.total_happiness() {
+            let updated: Float = if new_fitness > current_fitness {
                 // Happy case. We found a better solution.
-                updated = Float::new(1.0).unwrap();
+                current_fitness = new_fitness;
+                scorer.keep(plan, &change);
+                Float::new(1.0).unwrap()
             } else {
-                // Sad case. We need to go back by performing the same swap again.
-                make_swap(&mut plan, swap);
-                updated = Float::new(0.0).unwrap();
-            }
+                // Sad case. We need to go back.
+                scorer.reject(plan, &change);
+                Float::new(0.0).unwrap()
+            };
 
             update_ema = shift_ema(update_ema, updated);
         }
-        plan
+    }
+
+    // Makes improving moves until there are none left.
+    fn climb_exhaustive(&mut self, neighbourhood: &Neighbourhood, plan: &mut Plan) {
+        let problem = neighbourhood.problem;
+        let mut current_fitness = fitness(&self.objective, &Metrics::new(plan, problem));
+
+        loop {
+            let mut moves = neighbourhood.all_moves(&mut self.rng, plan);
+            moves.shuffle(&mut self.rng);
+
+            let mut best = None;
+            for change in moves {
+                make_move(plan, &change);
+                let new_fitness = fitness(&self.objective, &Metrics::new(plan, problem));
+                undo_move(plan, &change);
+
+                let best_fitness = best.as_ref().map_or(&current_fitness, |(f, _)| f);
+                if new_fitness > *best_fitness {
+                    best = Some((new_fitness, change));
+                    if self.mode == ClimbMode::FirstImprovement {
+                        break;
+                    }
+                }
+            }
+
+            match best {
+                Some((new_fitness, change)) => {
+                    make_move(plan, &change);
+                    current_fitness = new_fitness;
+                }
+                // We're at a local optimum.
+                None => return,
+            }
+        }
     }
 }
 
+impl<R, O> SeatingPlanner for HillClimbingPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
+}
+
+impl<R, O> LocalSearch for HillClimbingPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        let neighbourhood = Neighbourhood::new(problem)
+            .with_moves(self.moves)
+            .with_sampling(self.sampling);
+        self.climb(&neighbourhood, &mut plan);
+        check_feasible(plan, problem)
+    }
+}
+
+// How many past costs late acceptance remembers, unless told otherwise.
+const DEFAULT_HISTORY_LENGTH: usize = 1000;
+
+// Late acceptance can wander between equally good plans for ever, improving
+// on the current plan as often as it gets worse, so we also stop after this
+// many full histories without finding a better plan.
+const MAX_STALE_HISTORIES: usize = 10;
+
+/// Late Acceptance Hill Climbing: a move is kept if the plan is no worse
+/// than it is now, or than it was a fixed number of iterations ago.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub struct LahcPlanner<R> {
+pub struct LahcPlanner<R, O = TotalHappiness> {
     rng: R,
     // How far back do we look?
-    queue_size: NonZeroUsize,
+    history_length: NonZeroUsize,
     // How infrequent do updates need to get before we give up?
     termination_threshold: Float,
+    objective: O,
+    moves: MoveMix,
+    sampling: GuestSampling,
 }
 
 impl<R> LahcPlanner<R>
@@ -103,79 +431,652 @@ where
     R: Rng,
 {
     pub fn new(rng: R, termination_threshold: Float) -> Self {
+        Self::with_objective(rng, termination_threshold, TotalHappiness)
+    }
+}
+
+impl<R, O> LahcPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    pub fn with_objective(rng: R, termination_threshold: Float, objective: O) -> Self {
         Self {
             rng,
-            queue_size: NonZeroUsize::new(1000).unwrap(),
+            history_length: NonZeroUsize::new(DEFAULT_HISTORY_LENGTH).unwrap(),
             termination_threshold,
+            objective,
+            moves: MoveMix::default(),
+            sampling: GuestSampling::default(),
+        }
+    }
+
+    /// Sets how many iterations back a new plan is compared against.
+    /// Longer histories accept more worsening moves early on, which
+    /// explores more but takes longer to settle.
+    pub fn with_history_length(self, history_length: NonZeroUsize) -> Self {
+        Self {
+            history_length,
+            ..self
         }
     }
+
+    /// Chooses which kinds of random move are tried.
+    pub fn with_moves(self, moves: MoveMix) -> Self {
+        Self { moves, ..self }
+    }
+
+    /// Chooses how the guests to move are picked.
+    pub fn with_sampling(self, sampling: GuestSampling) -> Self {
+        Self { sampling, ..self }
+    }
+}
+
+impl<R, O> SeatingPlanner for LahcPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
 }
 
-impl<R> SeatingPlanner for LahcPlanner<R>
+impl<R, O> LocalSearch for LahcPlanner<R, O>
 where
     R: Rng,
+    O: Objective,
 {
-    fn plan(&mut self, problem: &Problem) -> Plan {
-        let relationships = &problem.relations;
-        let n_tables = problem.n_tables;
-        let table_size = relationships.len() / n_tables;
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        let neighbourhood = Neighbourhood::new(problem)
+            .with_moves(self.moves)
+            .with_sampling(self.sampling);
 
-        // Initialise our queue full of random solutions.
-        let mut queue = VecDeque::with_capacity(self.queue_size.get());
-        for _ in 0..self.queue_size.get() {
-            queue.push_back(random_plan(&mut self.rng, relationships.len(), n_tables))
-        }
+        let mut scorer = MoveScorer::new(&neighbourhood, &self.objective, &plan);
+        let mut current_fitness = scorer.fitness(&plan);
+
+        let mut best = plan.clone();
+        let mut best_fitness = current_fitness;
 
+        // The cost of the current plan at each of the last few iterations,
+        // used as a circular buffer.
+        let mut history = vec![current_fitness; self.history_length.get()];
+        let mut iteration = 0;
+        let mut best_iteration = 0;
+        let max_stale = MAX_STALE_HISTORIES * history.len();
+
+        // A moving average of how often we improve on the current plan.
         let mut update_ema = Float::new(1.0).unwrap();
 
-        while update_ema >= self.termination_threshold {
-            // Try a new solution and compare it to the front *and* back of our queue.
-            let mut new_plan = queue.back().cloned().expect("nonempty queue");
-            let swap = get_random_swap(&mut self.rng, n_tables, table_size);
-            make_swap(&mut new_plan, swap);
+        while update_ema >= self.termination_threshold && iteration - best_iteration < max_stale {
+            let late = iteration % history.len();
+            iteration += 1;
 
-            let new_metrics = Metrics::new(&new_plan, relationships);
-            let new_happiness = new_metrics.total_happiness();
+            let change = match neighbourhood.random_move(&mut self.rng, &plan) {
+                Some(change) => change,
+                None => {
+                    // Everyone we tried to move is pinned in place.
+                    update_ema = shift_ema(update_ema, Float::new(0.0).unwrap());
+                    continue;
+                }
+            };
+            let new_fitness = scorer.propose(&mut plan, &change);
 
-            let compare_to: [&Plan; 2] = [queue.front().unwrap(), queue.back().unwrap()];
-            let to_update = compare_to
-                .iter()
-                .any(|other| new_happiness > Metrics::new(other, relationships).total_happiness());
+            let updated: Float = if new_fitness > current_fitness {
+                Float::new(1.0).unwrap()
+            } else {
+                Float::new(0.0).unwrap()
+            };
 
-            let updated: Float;
-            if to_update {
-                queue.pop_front();
-                queue.push_back(new_plan);
-                updated = Float::new(1.0).unwrap();
+            // Accept anything no worse than now, or than the plan we had
+            // back at the start of the history.
+            if new_fitness >= current_fitness || new_fitness >= history[late] {
+                current_fitness = new_fitness;
+                scorer.keep(&mut plan, &change);
+                if current_fitness > best_fitness {
+                    best_fitness = current_fitness;
+                    best.clone_from(&plan);
+                    best_iteration = iteration;
+                }
             } else {
-                updated = Float::new(0.0).unwrap();
+                scorer.reject(&mut plan, &change);
             }
+            history[late] = current_fitness;
 
             update_ema = shift_ema(update_ema, updated);
         }
 
-        queue
+        check_feasible(best, problem)
+    }
+}
+
+impl<'a> Neighbourhood<'a> {
+    pub(crate) fn new(problem: &'a Problem) -> Self {
+        let units = problem.units();
+        let mut unit_of = vec![0; problem.relations().len()];
+        for (unit_id, unit) in units.iter().enumerate() {
+            for guest in unit {
+                unit_of[*guest] = unit_id;
+            }
+        }
+
+        let mut pinned = vec![false; problem.relations().len()];
+        for (guest, _) in problem.pins() {
+            pinned[*guest] = true;
+        }
+
+        Self {
+            problem,
+            unit_of,
+            units,
+            pinned,
+            moves: MoveMix::default(),
+            sampling: GuestSampling::default(),
+            sampler: RefCell::new(None),
+        }
+    }
+
+    pub(crate) fn with_moves(self, moves: MoveMix) -> Self {
+        Self { moves, ..self }
+    }
+
+    pub(crate) fn with_sampling(self, sampling: GuestSampling) -> Self {
+        Self { sampling, ..self }
+    }
+
+    // Picks the seat of a guest to move, or returns `None` if guests
+    // should be picked uniformly.
+    pub(crate) fn targeted_seat<R>(&self, rng: R, plan: &[Vec<usize>]) -> Option<(usize, usize)>
+    where
+        R: Rng,
+    {
+        if self.sampling == GuestSampling::Uniform {
+            return None;
+        }
+        let mut sampler = self.sampler.borrow_mut();
+        sampler
+            .get_or_insert_with(|| SeatSampler::new(self.sampling, self, plan))
+            .sample(rng)
+    }
+
+    // Forgets the chance of picking each seat, to be worked out again
+    // for the next plan moves are proposed from.
+    pub(crate) fn forget_plan(&self) {
+        self.sampler.replace(None);
+    }
+
+    // Updates the chance of picking each seat after a move is kept.
+    pub(crate) fn kept(&self, plan: &[Vec<usize>], change: &Move) {
+        if let Some(sampler) = self.sampler.borrow_mut().as_mut() {
+            sampler.update(self, plan, &touched_tables(change));
+        }
+    }
+
+    // Returns `None` if we couldn't find a move that keeps pinned
+    // guests in place.
+    pub(crate) fn random_move<R>(&self, mut rng: R, plan: &[Vec<usize>]) -> Option<Move>
+    where
+        R: Rng,
+    {
+        (0..MAX_PROPOSALS)
+            .filter_map(|_| {
+                let generator = self.moves.choose(&mut rng).generator();
+                generator.propose(self, &mut rng, plan)
+            })
+            .find(|change| self.is_allowed(plan, change))
+    }
+
+    // Swaps guests at different tables, or moves them into empty seats,
+    // along with their groups. When there's a layout, also swaps guests
+    // at the same table.
+    pub(crate) fn swap_or_relocation<R>(&self, mut rng: R, plan: &[Vec<usize>]) -> Option<Move>
+    where
+        R: Rng,
+    {
+        let targeted = self.targeted_seat(&mut rng, plan);
+
+        if self.problem.has_empty_seats() && rng.gen_bool(RELOCATION_PROBABILITY) {
+            let relocation = match targeted {
+                Some(seat) => get_relocation_from(&mut rng, self.problem, plan, seat),
+                None => get_random_relocation(&mut rng, self.problem, plan),
+            };
+            if let Some(relocation) = relocation {
+                let guest = plan[relocation.from_table][relocation.seat];
+                return if self.group(guest).len() == 1 {
+                    Some(Move::Relocate(relocation))
+                } else {
+                    self.group_relocation(plan, guest, relocation)
+                };
+            }
+        }
+
+        if self.problem.layout.is_some() && rng.gen_bool(SEAT_SWAP_PROBABILITY) {
+            return get_random_seat_swap(rng, plan).map(Move::Swap);
+        }
+
+        let swap = match targeted {
+            Some(seat) => get_swap_from(&mut rng, plan, seat)?,
+            None => get_random_swap(&mut rng, plan)?,
+        };
+        self.swap_move(rng, plan, swap)
+    }
+
+    // Every move that swaps two seats or fills an empty seat, extended
+    // to whole groups where needed, that keeps pinned guests in place.
+    // Groups are swapped with randomly chosen guests from the other table.
+    pub(crate) fn all_moves<R>(&self, mut rng: R, plan: &[Vec<usize>]) -> Vec<Move>
+    where
+        R: Rng,
+    {
+        let seats: Vec<(usize, usize)> = plan
+            .iter()
+            .enumerate()
+            .flat_map(|(table, guests)| (0..guests.len()).map(move |seat| (table, seat)))
+            .collect();
+        let same_table_matters = self.problem.layout.is_some();
+
+        let mut moves = Vec::new();
+        for (i, &(table1, seat1)) in seats.iter().enumerate() {
+            for &(table2, seat2) in &seats[i + 1..] {
+                if table1 == table2 && !same_table_matters {
+                    continue;
+                }
+                let swap = Swap {
+                    table1,
+                    seat1,
+                    table2,
+                    seat2,
+                };
+                moves.extend(self.swap_move(&mut rng, plan, swap));
+            }
+
+            let has_room = |table: usize| plan[table].len() < self.problem.capacity(table);
+            if plan[table1].len() > self.problem.min_occupancy {
+                for to_table in (0..plan.len()).filter(|t| *t != table1 && has_room(*t)) {
+                    let relocation = Relocation {
+                        from_table: table1,
+                        seat: seat1,
+                        to_table,
+                    };
+                    let guest = plan[table1][seat1];
+                    if self.group(guest).len() == 1 {
+                        moves.push(Move::Relocate(relocation));
+                    } else {
+                        moves.extend(self.group_relocation(plan, guest, relocation));
+                    }
+                }
+            }
+        }
+
+        moves.retain(|change| self.is_allowed(plan, change));
+        moves
+    }
+
+    // Extends a swap of two guests to their whole groups, if either is in one.
+    fn swap_move<R>(&self, rng: R, plan: &[Vec<usize>], swap: Swap) -> Option<Move>
+    where
+        R: Rng,
+    {
+        let guest1 = plan[swap.table1][swap.seat1];
+        let guest2 = plan[swap.table2][swap.seat2];
+        if swap.table1 == swap.table2 {
+            // Changing seats at the same table never splits a group.
+            Some(Move::Swap(swap))
+        } else if self.group(guest1).len() > 1 {
+            self.group_swap(rng, plan, guest1, swap.table1, swap.table2)
+        } else if self.group(guest2).len() > 1 {
+            self.group_swap(rng, plan, guest2, swap.table2, swap.table1)
+        } else {
+            Some(Move::Swap(swap))
+        }
+    }
+
+    // The guests who must move with this guest, including themselves.
+    pub(crate) fn group(&self, guest: usize) -> &[usize] {
+        &self.units[self.unit_of[guest]]
+    }
+
+    // Swaps the guest's group with some whole groups (often single guests)
+    // from the other table, adding up to the same number of people.
+    fn group_swap<R>(
+        &self,
+        mut rng: R,
+        plan: &[Vec<usize>],
+        guest: usize,
+        table: usize,
+        other_table: usize,
+    ) -> Option<Move>
+    where
+        R: Rng,
+    {
+        if table == other_table {
+            return None;
+        }
+        let group = self.group(guest);
+
+        let mut candidates: Vec<usize> = plan[other_table]
+            .iter()
+            .map(|other| self.unit_of[*other])
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates.shuffle(&mut rng);
+
+        let mut n_needed = group.len();
+        let mut others = Vec::with_capacity(group.len());
+        for unit in candidates {
+            let unit = &self.units[unit];
+            if unit.len() <= n_needed {
+                n_needed -= unit.len();
+                others.extend_from_slice(unit);
+            }
+        }
+        if n_needed > 0 {
+            return None;
+        }
+
+        Some(Move::SwapGroup(GroupSwap {
+            table1: table,
+            seats1: seats_of(&plan[table], group),
+            table2: other_table,
+            seats2: seats_of(&plan[other_table], &others),
+        }))
+    }
+
+    // Extends a relocation of one guest to their whole group, if there's room.
+    fn group_relocation(
+        &self,
+        plan: &[Vec<usize>],
+        guest: usize,
+        relocation: Relocation,
+    ) -> Option<Move> {
+        let group = self.group(guest);
+        let from_table = relocation.from_table;
+        let to_table = relocation.to_table;
+
+        let enough_left = plan[from_table].len() >= self.problem.min_occupancy + group.len();
+        let enough_room = plan[to_table].len() + group.len() <= self.problem.capacity(to_table);
+        if !(enough_left && enough_room) {
+            return None;
+        }
+
+        let mut seats = seats_of(&plan[from_table], group);
+        seats.sort_unstable();
+        Some(Move::RelocateGroup(GroupRelocation {
+            from_table,
+            seats,
+            to_table,
+        }))
+    }
+
+    // Pinned guests never move, and nobody moves to a table they
+    // are forbidden from.
+    pub(crate) fn can_move(&self, guest: usize, table: usize) -> bool {
+        !self.pinned[guest] && self.problem.allows(guest, table)
+    }
+
+    pub(crate) fn is_pinned(&self, guest: usize) -> bool {
+        self.pinned[guest]
+    }
+
+    fn is_allowed(&self, plan: &[Vec<usize>], change: &Move) -> bool {
+        moved_guests(plan, change)
             .into_iter()
-            .max_by_key(|plan| Metrics::new(plan, &relationships).total_happiness())
-            .expect("Queue length is not zero.")
+            .all(|(guest, table)| self.can_move(guest, table))
+    }
+}
+
+// Lists everyone the move affects, along with the table they end up at.
+pub(crate) fn moved_guests(plan: &[Vec<usize>], change: &Move) -> Vec<(usize, usize)> {
+    match change {
+        Move::Swap(swap) => vec![
+            (plan[swap.table1][swap.seat1], swap.table2),
+            (plan[swap.table2][swap.seat2], swap.table1),
+        ],
+        Move::Relocate(relocation) => vec![(
+            plan[relocation.from_table][relocation.seat],
+            relocation.to_table,
+        )],
+        Move::SwapGroup(swap) => {
+            let there = swap
+                .seats1
+                .iter()
+                .map(|s| (plan[swap.table1][*s], swap.table2));
+            let back = swap
+                .seats2
+                .iter()
+                .map(|s| (plan[swap.table2][*s], swap.table1));
+            there.chain(back).collect()
+        }
+        Move::RelocateGroup(relocation) => relocation
+            .seats
+            .iter()
+            .map(|s| (plan[relocation.from_table][*s], relocation.to_table))
+            .collect(),
+        Move::Chain(chain) => {
+            let last_table = chain.end_table.unwrap_or(chain.seats[0].0);
+            let to_tables = chain.seats[1..]
+                .iter()
+                .map(|(table, _)| *table)
+                .chain(Some(last_table));
+            chain
+                .seats
+                .iter()
+                .map(|(table, seat)| plan[*table][*seat])
+                .zip(to_tables)
+                .collect()
+        }
+    }
+}
+
+// Lists the tables the move changes.
+fn touched_tables(change: &Move) -> Vec<usize> {
+    match change {
+        Move::Swap(swap) => vec![swap.table1, swap.table2],
+        Move::Relocate(relocation) => vec![relocation.from_table, relocation.to_table],
+        Move::SwapGroup(swap) => vec![swap.table1, swap.table2],
+        Move::RelocateGroup(relocation) => vec![relocation.from_table, relocation.to_table],
+        Move::Chain(chain) => chain
+            .seats
+            .iter()
+            .map(|(table, _)| *table)
+            .chain(chain.end_table)
+            .collect(),
     }
 }
 
-fn get_random_swap<R>(mut rng: R, n_tables: usize, table_size: usize) -> Swap
+// Finds where each of the guests sits at the table.
+fn seats_of(table: &[usize], guests: &[usize]) -> Vec<usize> {
+    guests
+        .iter()
+        .map(|guest| {
+            table
+                .iter()
+                .position(|other| other == guest)
+                .expect("Groups are always seated together.")
+        })
+        .collect()
+}
+
+// Swaps two guests at different tables, as swapping guests at the same
+// table changes nothing. Returns `None` if only one table has anyone at it.
+fn get_random_swap<R>(mut rng: R, plan: &[Vec<usize>]) -> Option<Swap>
 where
     R: Rng,
 {
-    let table1 = rng.gen_range(0..n_tables);
-    let table2 = rng.gen_range(0..n_tables);
+    let tables = occupied_tables(plan);
+    let (table1, table2) = match tables.choose_multiple(&mut rng, 2).collect::<Vec<_>>()[..] {
+        [table1, table2] => (*table1, *table2),
+        _ => return None,
+    };
 
-    let seat1 = rng.gen_range(0..table_size);
-    let seat2 = rng.gen_range(0..table_size);
+    // Tables may be different sizes, so pick seats that exist.
+    let seat1 = rng.gen_range(0..plan[table1].len());
+    let seat2 = rng.gen_range(0..plan[table2].len());
 
-    Swap {
+    Some(Swap {
         table1,
         table2,
         seat1,
         seat2,
+    })
+}
+
+// Swaps the guest in this seat with someone at another table.
+fn get_swap_from<R>(
+    mut rng: R,
+    plan: &[Vec<usize>],
+    (table1, seat1): (usize, usize),
+) -> Option<Swap>
+where
+    R: Rng,
+{
+    let tables: Vec<usize> = occupied_tables(plan)
+        .into_iter()
+        .filter(|t| *t != table1)
+        .collect();
+    let table2 = *tables.choose(&mut rng)?;
+    let seat2 = rng.gen_range(0..plan[table2].len());
+
+    Some(Swap {
+        table1,
+        table2,
+        seat1,
+        seat2,
+    })
+}
+
+// Swaps two guests at the same table, which only changes who
+// they sit next to. Returns `None` if nobody has anyone to swap with.
+fn get_random_seat_swap<R>(mut rng: R, plan: &[Vec<usize>]) -> Option<Swap>
+where
+    R: Rng,
+{
+    let tables: Vec<usize> = (0..plan.len()).filter(|t| plan[*t].len() > 1).collect();
+    let table = *tables.choose(&mut rng)?;
+    let seats = rand::seq::index::sample(&mut rng, plan[table].len(), 2);
+
+    Some(Swap {
+        table1: table,
+        table2: table,
+        seat1: seats.index(0),
+        seat2: seats.index(1),
+    })
+}
+
+// Returns `None` if no guest can be moved without leaving their table
+// below the minimum occupancy, or if there are no empty seats.
+fn get_random_relocation<R>(
+    mut rng: R,
+    problem: &Problem,
+    plan: &[Vec<usize>],
+) -> Option<Relocation>
+where
+    R: Rng,
+{
+    let from_tables: Vec<usize> = (0..plan.len())
+        .filter(|t| plan[*t].len() > problem.min_occupancy)
+        .collect();
+    let from_table = *from_tables.choose(&mut rng)?;
+
+    let to_tables: Vec<usize> = (0..plan.len())
+        .filter(|t| *t != from_table && plan[*t].len() < problem.capacity(*t))
+        .collect();
+    let to_table = *to_tables.choose(&mut rng)?;
+
+    let seat = rng.gen_range(0..plan[from_table].len());
+
+    Some(Relocation {
+        from_table,
+        seat,
+        to_table,
+    })
+}
+
+// Moves the guest in this seat to another table with room. Returns `None`
+// if that would leave their table below the minimum occupancy.
+fn get_relocation_from<R>(
+    mut rng: R,
+    problem: &Problem,
+    plan: &[Vec<usize>],
+    (from_table, seat): (usize, usize),
+) -> Option<Relocation>
+where
+    R: Rng,
+{
+    if plan[from_table].len() <= problem.min_occupancy {
+        return None;
+    }
+    let to_tables: Vec<usize> = (0..plan.len())
+        .filter(|t| *t != from_table && plan[*t].len() < problem.capacity(*t))
+        .collect();
+    let to_table = *to_tables.choose(&mut rng)?;
+
+    Some(Relocation {
+        from_table,
+        seat,
+        to_table,
+    })
+}
+
+pub(crate) fn occupied_tables(plan: &[Vec<usize>]) -> Vec<usize> {
+    (0..plan.len()).filter(|t| !plan[*t].is_empty()).collect()
+}
+
+pub(crate) fn make_move(plan: &mut [Vec<usize>], change: &Move) {
+    match change {
+        Move::Swap(swap) => make_swap(plan, *swap),
+        Move::Relocate(relocation) => make_relocation(plan, *relocation),
+        Move::SwapGroup(swap) => make_group_swap(plan, swap),
+        Move::RelocateGroup(relocation) => make_group_relocation(plan, relocation),
+        Move::Chain(chain) => make_chain(plan, chain),
+    }
+}
+
+// Puts everyone back where they were before the move.
+pub(crate) fn undo_move(plan: &mut [Vec<usize>], change: &Move) {
+    match change {
+        // Swapping back is the same as swapping again.
+        Move::Swap(swap) => make_swap(plan, *swap),
+        Move::SwapGroup(swap) => make_group_swap(plan, swap),
+        Move::Relocate(relocation) => {
+            let guest = plan[relocation.to_table]
+                .pop()
+                .expect("The relocated guest is at the end of the table.");
+            plan[relocation.from_table].insert(relocation.seat, guest);
+        }
+        Move::RelocateGroup(relocation) => {
+            let to_table = &mut plan[relocation.to_table];
+            let guests = to_table.split_off(to_table.len() - relocation.seats.len());
+            // Insert from the front, so the later seat numbers are correct.
+            for (seat, guest) in relocation.seats.iter().zip(guests) {
+                plan[relocation.from_table].insert(*seat, guest);
+            }
+        }
+        Move::Chain(chain) => {
+            let (first_table, first_seat) = chain.seats[0];
+            // Everyone but the last guest is one seat along.
+            let mut guests: Vec<usize> = chain.seats[1..]
+                .iter()
+                .map(|(table, seat)| plan[*table][*seat])
+                .collect();
+            guests.push(match chain.end_table {
+                Some(end_table) => plan[end_table]
+                    .pop()
+                    .expect("The last guest is at the end of the table."),
+                None => plan[first_table][first_seat],
+            });
+
+            match chain.end_table {
+                Some(_) => plan[first_table].insert(first_seat, guests[0]),
+                None => plan[first_table][first_seat] = guests[0],
+            }
+            for ((table, seat), guest) in chain.seats.iter().zip(guests).skip(1) {
+                plan[*table][*seat] = guest;
+            }
+        }
     }
 }
 
@@ -185,34 +1086,156 @@ fn make_swap(plan: &mut [Vec<usize>], swap: Swap) {
     plan[swap.table2][swap.seat2] = tmp;
 }
 
-fn random_plan<R>(mut rng: R, n_guests: usize, n_tables: usize) -> Plan
+fn make_relocation(plan: &mut [Vec<usize>], relocation: Relocation) {
+    let guest = plan[relocation.from_table].remove(relocation.seat);
+    plan[relocation.to_table].push(guest);
+}
+
+fn make_group_swap(plan: &mut [Vec<usize>], swap: &GroupSwap) {
+    for (seat1, seat2) in swap.seats1.iter().zip(&swap.seats2) {
+        let tmp = plan[swap.table1][*seat1];
+        plan[swap.table1][*seat1] = plan[swap.table2][*seat2];
+        plan[swap.table2][*seat2] = tmp;
+    }
+}
+
+fn make_chain(plan: &mut [Vec<usize>], chain: &Chain) {
+    let guests: Vec<usize> = chain
+        .seats
+        .iter()
+        .map(|(table, seat)| plan[*table][*seat])
+        .collect();
+    for ((table, seat), guest) in chain.seats[1..].iter().zip(&guests) {
+        plan[*table][*seat] = *guest;
+    }
+
+    let (first_table, first_seat) = chain.seats[0];
+    let last = *guests.last().expect("Chains move at least one guest.");
+    match chain.end_table {
+        Some(end_table) => {
+            plan[first_table].remove(first_seat);
+            plan[end_table].push(last);
+        }
+        None => plan[first_table][first_seat] = last,
+    }
+}
+
+fn make_group_relocation(plan: &mut [Vec<usize>], relocation: &GroupRelocation) {
+    let from_table = &mut plan[relocation.from_table];
+    let guests: Vec<usize> = relocation.seats.iter().map(|s| from_table[*s]).collect();
+    // Remove from the back, so the remaining seat numbers stay correct.
+    for seat in relocation.seats.iter().rev() {
+        from_table.remove(*seat);
+    }
+    plan[relocation.to_table].extend(guests);
+}
+
+pub(crate) fn random_plan<R>(mut rng: R, problem: &Problem) -> Result<Plan, PlanError>
+where
+    R: Rng,
+{
+    // Packing groups of different sizes around the tables can fail
+    // by bad luck, so have a few goes.
+    for _ in 0..MAX_PACKING_ATTEMPTS {
+        if let Some(plan) = try_random_plan(&mut rng, problem) {
+            return Ok(plan);
+        }
+    }
+    Err(PlanError::Unpackable)
+}
+
+fn try_random_plan<R>(mut rng: R, problem: &Problem) -> Option<Plan>
 where
     R: Rng,
 {
-    assert_eq!(n_guests % n_tables, 0);
+    let n_guests = problem.relations().len();
+
+    let mut apart_from = vec![Vec::new(); n_guests];
+    for &(i, j) in problem.apart() {
+        apart_from[i].push(j);
+        apart_from[j].push(i);
+    }
+
+    let can_sit_at =
+        |unit: &[usize], table: usize| unit.iter().all(|guest| problem.allows(*guest, table));
+
+    // Shuffle the groups who must sit together, then seat the most
+    // restricted and the biggest groups first while there is still room.
+    let mut units = problem.units();
+    units.shuffle(&mut rng);
+    units.sort_by_cached_key(|unit| {
+        let n_allowed = (0..problem.n_tables)
+            .filter(|t| can_sit_at(unit, *t))
+            .count();
+        (n_allowed, Reverse(unit.len()))
+    });
 
-    // Generate a random permutation of guests.
-    let mut permutation = (0..n_guests).collect::<Vec<usize>>();
-    permutation.shuffle(&mut rng);
+    let mut plan = vec![Vec::new(); problem.n_tables];
+    let mut seated_at = vec![None; n_guests];
+    let mut table_order: Vec<usize> = (0..problem.n_tables).collect();
 
-    // Chunk the guests into tables.
-    let table_size = n_guests / n_tables;
-    permutation
-        .chunks_exact(table_size)
-        .map(ToOwned::to_owned)
-        .collect::<Vec<Vec<usize>>>()
+    for unit in units {
+        // Prefer tables that are below their minimum occupancy, then tables
+        // with the fewest people this group must be kept apart from.
+        table_order.shuffle(&mut rng);
+        let table = table_order
+            .iter()
+            .copied()
+            .filter(|t| plan[*t].len() + unit.len() <= problem.capacity(*t))
+            .filter(|t| can_sit_at(&unit, *t))
+            .min_by_key(|t| {
+                let n_conflicts = unit
+                    .iter()
+                    .flat_map(|guest| &apart_from[*guest])
+                    .filter(|other| seated_at[**other] == Some(*t))
+                    .count();
+                (plan[*t].len() >= problem.min_occupancy, n_conflicts)
+            })?;
+
+        for guest in unit {
+            seated_at[guest] = Some(table);
+            plan[table].push(guest);
+        }
+    }
+
+    if plan
+        .iter()
+        .all(|table| table.len() >= problem.min_occupancy)
+    {
+        Some(plan)
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::objective::{FewestLonely, Lexicographic, Maximin};
+    use crate::test_util::{
+        assert_feasible, assert_improves_on_start, assert_partition, constrained_wedding,
+        random_wedding,
+    };
+    use crate::{AdjacencyWeights, GuestRelations, Layout, TableShape};
+
+    const MODES: [ClimbMode; 3] = [
+        ClimbMode::Stochastic,
+        ClimbMode::FirstImprovement,
+        ClimbMode::SteepestAscent,
+    ];
+
+    fn indifferent(n_guests: usize) -> GuestRelations {
+        GuestRelations::new(vec![vec![0; n_guests]; n_guests]).unwrap()
+    }
+
     #[test]
     fn plan_random_init() {
         let n_tables = 12;
         let table_size = 5;
         let n_guests = n_tables * table_size;
-        let plan = random_plan(thread_rng(), n_guests, n_tables);
+        let problem = Problem::new(indifferent(n_guests), n_tables).unwrap();
+        let plan = random_plan(thread_rng(), &problem).unwrap();
 
         // Correct number of tables.
         assert_eq!(plan.len(), n_tables);
@@ -222,15 +1245,339 @@ mod tests {
             assert_eq!(table.len(), table_size);
         }
 
-        // Check that each guest appears exactly once.
-        let mut guest_appearances = vec![0; n_guests];
-        for table in &plan {
-            for guest in table {
-                guest_appearances[*guest] += 1;
+        assert_partition(&plan, n_guests);
+    }
+
+    #[test]
+    fn plan_random_init_capacities() {
+        let capacities = vec![8, 10, 12, 3];
+        let n_guests = capacities.iter().sum();
+        let problem = Problem::with_capacities(indifferent(n_guests), capacities.clone()).unwrap();
+        let mut plan = random_plan(thread_rng(), &problem).unwrap();
+
+        let sizes: Vec<usize> = plan.iter().map(Vec::len).collect();
+        assert_eq!(sizes, capacities);
+        assert_partition(&plan, n_guests);
+
+        // Swapping never changes the size of a table.
+        for _ in 0..1000 {
+            let swap = get_random_swap(thread_rng(), &plan).unwrap();
+            assert_ne!(swap.table1, swap.table2);
+            make_swap(&mut plan, swap);
+        }
+        let sizes: Vec<usize> = plan.iter().map(Vec::len).collect();
+        assert_eq!(sizes, capacities);
+        assert_partition(&plan, n_guests);
+    }
+
+    #[test]
+    fn plan_random_init_empty_seats() {
+        let n_guests = 97;
+        let problem = Problem::new(indifferent(n_guests), 10)
+            .unwrap()
+            .with_min_occupancy(8)
+            .unwrap();
+        let mut plan = random_plan(thread_rng(), &problem).unwrap();
+
+        let in_bounds = |plan: &Plan| {
+            plan.iter()
+                .all(|table| table.len() >= 8 && table.len() <= 10)
+        };
+        assert!(in_bounds(&plan));
+        assert_partition(&plan, n_guests);
+
+        // Moves never overfill or underfill a table.
+        let mut n_relocations = 0;
+        for _ in 0..1000 {
+            let change = Neighbourhood::new(&problem)
+                .random_move(thread_rng(), &plan)
+                .unwrap();
+            if let Move::Relocate(_) = change {
+                n_relocations += 1;
+            }
+            make_move(&mut plan, &change);
+        }
+        assert!(n_relocations > 0);
+        assert!(in_bounds(&plan));
+        assert_partition(&plan, n_guests);
+    }
+
+    #[test]
+    fn plan_random_init_constraints() {
+        let problem = Problem::new(indifferent(12), 3)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1, 2], vec![3, 4]], vec![(0, 3), (5, 6)])
+            .unwrap();
+
+        for _ in 0..100 {
+            let plan = random_plan(thread_rng(), &problem).unwrap();
+            assert_partition(&plan, 12);
+            // Only `apart` constraints are left for the planners to fix.
+            assert_eq!(Metrics::new(&plan, &problem).together_violations(), 0);
+        }
+    }
+
+    #[test]
+    fn lahc_respects_constraints() {
+        // Everyone wants to sit with guest 0, but guest 1 can't.
+        let relations = (0..6)
+            .map(|i| {
+                (0..6)
+                    .map(|j| if (i == 0) != (j == 0) { 10 } else { 0 })
+                    .collect()
+            })
+            .collect();
+        let problem = Problem::new(GuestRelations::new(relations).unwrap(), 2)
+            .unwrap()
+            .with_constraints(vec![vec![1, 2]], vec![(0, 1)])
+            .unwrap();
+
+        let mut planner = LahcPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap());
+        let plan = planner.plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+
+        let problem = constrained_wedding(0);
+        let mut planner = LahcPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap());
+        let plan = planner.plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+    }
+
+    #[test]
+    fn pinned_guests_never_move() {
+        let problem = Problem::new(indifferent(12), 3)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1]], vec![])
+            .unwrap()
+            .with_pins(vec![(0, 2), (5, 0)], vec![(6, 0), (6, 1)])
+            .unwrap();
+
+        let neighbourhood = Neighbourhood::new(&problem);
+        let mut plan = random_plan(thread_rng(), &problem).unwrap();
+        for _ in 0..1000 {
+            if let Some(change) = neighbourhood.random_move(thread_rng(), &plan) {
+                make_move(&mut plan, &change);
+            }
+            assert!(plan[2].contains(&0));
+            assert!(plan[0].contains(&5));
+            assert!(plan[2].contains(&6));
+        }
+        assert_partition(&plan, 12);
+    }
+
+    #[test]
+    fn groups_move_together() {
+        let problem = Problem::with_capacities(indifferent(20), vec![6, 6, 6, 4])
+            .unwrap()
+            .with_constraints(vec![vec![0, 1], vec![2, 3, 4], vec![5, 6, 7, 8]], vec![])
+            .unwrap();
+        let neighbourhood = Neighbourhood::new(&problem);
+
+        let mut plan = random_plan(thread_rng(), &problem).unwrap();
+        let mut n_group_moves = 0;
+        for _ in 0..1000 {
+            let change = neighbourhood.random_move(thread_rng(), &plan).unwrap();
+            if let Move::SwapGroup(_) | Move::RelocateGroup(_) = change {
+                n_group_moves += 1;
+            }
+            make_move(&mut plan, &change);
+            assert_eq!(Metrics::new(&plan, &problem).together_violations(), 0);
+        }
+        assert!(n_group_moves > 0);
+        assert!(plan
+            .iter()
+            .zip(problem.capacities())
+            .all(|(t, c)| t.len() <= c));
+        assert_partition(&plan, 20);
+    }
+
+    #[test]
+    fn split_groups_are_rejected() {
+        let problem = Problem::new(indifferent(4), 2)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1]], vec![])
+            .unwrap();
+        let split = vec![vec![0, 2], vec![1, 3]];
+
+        let mut planner =
+            HillClimbingPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap());
+        assert_eq!(
+            planner.improve(&problem, split.clone()),
+            Err(PlanError::SplitGroup)
+        );
+        let mut planner = LahcPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap());
+        assert_eq!(planner.improve(&problem, split), Err(PlanError::SplitGroup));
+    }
+
+    #[test]
+    fn lahc_orders_seats() {
+        // Guests 0 and 1 only care about sitting next to each other, which
+        // can only happen by changing seats at a single long table.
+        let mut relations = vec![vec![0; 8]; 8];
+        relations[0][1] = 10;
+        relations[1][0] = 10;
+        let layout = Layout {
+            shapes: vec![TableShape::Banquet],
+            weights: AdjacencyWeights {
+                neighbour: 1,
+                across: 0,
+                same_table: 0,
+            },
+        };
+        let problem = Problem::new(GuestRelations::new(relations).unwrap(), 1)
+            .unwrap()
+            .with_layout(layout)
+            .unwrap();
+
+        let mut planner = LahcPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap());
+        let plan = planner.plan(&problem).unwrap();
+        assert_partition(&plan, 8);
+        assert_eq!(Metrics::new(&plan, &problem).total_happiness(), 20);
+    }
+
+    #[test]
+    fn lahc_maximin() {
+        // Guests 1 and 2 are best friends, but seating them together
+        // leaves guests 0 and 3 with nobody they know.
+        let relations = GuestRelations::new(vec![
+            vec![0, 1, 0, 0],
+            vec![1, 0, 10, 0],
+            vec![0, 10, 0, 1],
+            vec![0, 0, 1, 0],
+        ])
+        .unwrap();
+        let problem = Problem::new(relations, 2).unwrap();
+
+        let mut planner = LahcPlanner::with_objective(
+            StdRng::seed_from_u64(0),
+            Float::new(0.01).unwrap(),
+            Maximin,
+        );
+        let plan = planner.plan(&problem).unwrap();
+        assert_eq!(Metrics::new(&plan, &problem).min_happiness(), 1);
+    }
+
+    #[test]
+    fn lahc_fairness() {
+        // The same wedding as above: maximising total happiness alone
+        // seats 1 and 2 together and leaves 0 and 3 lonely.
+        let relations = GuestRelations::new(vec![
+            vec![0, 1, 0, 0],
+            vec![1, 0, 10, 0],
+            vec![0, 10, 0, 1],
+            vec![0, 0, 1, 0],
+        ])
+        .unwrap();
+        let problem = Problem::new(relations, 2).unwrap();
+
+        let mut planner = LahcPlanner::with_objective(
+            StdRng::seed_from_u64(0),
+            Float::new(0.01).unwrap(),
+            Lexicographic(FewestLonely, TotalHappiness),
+        );
+        let plan = planner.plan(&problem).unwrap();
+        assert_eq!(Metrics::new(&plan, &problem).n_lonely(), 0);
+
+        // Requiring friends makes it a hard constraint, whatever the objective.
+        let problem = problem.with_friend_required();
+        let mut planner = LahcPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap());
+        let plan = planner.plan(&problem).unwrap();
+        let metrics = Metrics::new(&plan, &problem);
+        assert_eq!(metrics.friendless_violations(), 0);
+        assert_eq!(metrics.n_lonely(), 0);
+    }
+
+    #[test]
+    fn friends_are_weighted_by_layout() {
+        // Guests 0 and 3 like each other, but it only counts if they sit
+        // next to each other.
+        let mut relations = vec![vec![0; 4]; 4];
+        relations[0][3] = 1;
+        relations[3][0] = 1;
+        let layout = Layout {
+            shapes: vec![TableShape::Banquet],
+            weights: AdjacencyWeights {
+                neighbour: 1,
+                across: 0,
+                same_table: 0,
+            },
+        };
+        let problem = Problem::new(GuestRelations::new(relations).unwrap(), 1)
+            .unwrap()
+            .with_layout(layout)
+            .unwrap()
+            .with_friend_required();
+
+        let apart = Metrics::new(&vec![vec![0, 1, 2, 3]], &problem);
+        assert_eq!(apart.friendless_violations(), 2);
+        assert_eq!(apart.n_lonely(), 4);
+        let adjacent = Metrics::new(&vec![vec![0, 3, 1, 2]], &problem);
+        assert_eq!(adjacent.friendless_violations(), 0);
+        assert_eq!(adjacent.n_lonely(), 2);
+    }
+
+    #[test]
+    fn hill_climbing_never_worse_than_start() {
+        for seed in 0..5 {
+            for mode in MODES {
+                assert_improves_on_start(seed, 20, 4, |rng| {
+                    HillClimbingPlanner::new(rng, Float::new(0.01).unwrap()).with_mode(mode)
+                });
             }
         }
-        for n in guest_appearances {
-            assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn hill_climbing_respects_constraints() {
+        let problem = constrained_wedding(0);
+        for mode in MODES {
+            let mut planner =
+                HillClimbingPlanner::new(StdRng::seed_from_u64(0), Float::new(0.01).unwrap())
+                    .with_mode(mode);
+            let plan = planner.plan(&problem).unwrap();
+            assert_feasible(&plan, &problem);
+        }
+    }
+
+    #[test]
+    fn lahc_never_worse_than_start() {
+        for (seed, history_length) in [(0, 1), (1, 10), (2, 100), (3, 1000)] {
+            assert_improves_on_start(seed, 20, 4, |rng| {
+                LahcPlanner::new(rng, Float::new(0.01).unwrap())
+                    .with_history_length(NonZeroUsize::new(history_length).unwrap())
+            });
+        }
+    }
+
+    #[test]
+    fn steepest_ascent_finds_local_optimum() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let problem = random_wedding(&mut rng, 15, 3);
+        let mut planner = HillClimbingPlanner::new(&mut rng, Float::new(0.01).unwrap())
+            .with_mode(ClimbMode::SteepestAscent);
+        let mut plan = planner.plan(&problem).unwrap();
+        let total = Metrics::new(&plan, &problem).total_happiness();
+
+        for change in Neighbourhood::new(&problem).all_moves(&mut rng, &plan) {
+            make_move(&mut plan, &change);
+            assert!(Metrics::new(&plan, &problem).total_happiness() <= total);
+            undo_move(&mut plan, &change);
+        }
+    }
+
+    #[test]
+    fn undo_restores_plan() {
+        let problem = Problem::new(indifferent(23), 4)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1, 2], vec![3, 4]], vec![])
+            .unwrap();
+        let plan = random_plan(thread_rng(), &problem).unwrap();
+        let neighbourhood = Neighbourhood::new(&problem);
+
+        for change in neighbourhood.all_moves(thread_rng(), &plan) {
+            let mut changed = plan.clone();
+            make_move(&mut changed, &change);
+            undo_move(&mut changed, &change);
+            assert_eq!(changed, plan);
         }
     }
 }
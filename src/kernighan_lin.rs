@@ -0,0 +1,466 @@
+use crate::hill_climb::{check_feasible, check_groups, random_plan};
+use crate::{LocalSearch, Plan, PlanError, Problem, SeatingPlanner};
+
+use rand::prelude::*;
+use std::ops::AddAssign;
+
+// The most times we go through every pair of tables, in case a pass keeps
+// finding improvements too small to matter.
+const MAX_SWEEPS: usize = 100;
+
+/// Kernighan-Lin refinement, with Fiduccia-Mattheyses style moves into empty
+/// seats. Each pass looks at two tables, and keeps making the best swap or
+/// move between them, even if it makes things worse, without moving anyone
+/// twice. Then it goes back to the best point in the pass. Passes are made
+/// over every pair of tables until none of them helps.
+///
+/// Moves that fix broken hard constraints come before any gain in
+/// happiness, so a starting plan that breaks some gets repaired on the way.
+/// This maximises total happiness, and can polish a plan from another
+/// planner through `WarmStart`. It doesn't know about seating layouts.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct KernighanLinPlanner<R> {
+    rng: R,
+}
+
+impl<R> KernighanLinPlanner<R>
+where
+    R: Rng,
+{
+    /// The random number generator is only used to make the starting plan,
+    /// when the planner isn't given one.
+    pub fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R> SeatingPlanner for KernighanLinPlanner<R>
+where
+    R: Rng,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
+}
+
+impl<R> LocalSearch for KernighanLinPlanner<R>
+where
+    R: Rng,
+{
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        if problem.layout.is_some() {
+            return Err(PlanError::Unsupported(
+                "Kernighan-Lin doesn't know about seating layouts",
+            ));
+        }
+
+        let mut refinement = Refinement::new(problem, &plan);
+        for _ in 0..MAX_SWEEPS {
+            let mut improved = false;
+            for table1 in 0..plan.len() {
+                for table2 in table1 + 1..plan.len() {
+                    if refinement.pass(&mut plan, table1, table2) > Gain::default() {
+                        improved = true;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        check_feasible(plan, problem)
+    }
+}
+
+// What a move is worth. Fixing hard constraints matters more than any
+// amount of happiness, so it's compared first.
+#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct Gain {
+    // How many fewer hard constraints are broken.
+    repaired: i64,
+    // How much total happiness goes up.
+    happiness: i64,
+}
+
+impl AddAssign for Gain {
+    fn add_assign(&mut self, other: Self) {
+        self.repaired += other.repaired;
+        self.happiness += other.happiness;
+    }
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+enum KlMove {
+    // Two units trade tables.
+    Swap(usize, usize),
+    // A unit moves from one table to another.
+    Relocation { unit: usize, from: usize, to: usize },
+}
+
+struct Refinement<'a> {
+    problem: &'a Problem,
+    // The groups who must sit together.
+    units: Vec<Vec<usize>>,
+    // Index by guest to get their unit.
+    unit_of: Vec<usize>,
+    // Index by unit to get whether anyone in it is pinned in place.
+    pinned: Vec<bool>,
+    // Index by guest to get the table they're at.
+    table_of: Vec<usize>,
+    apart_from: Vec<Vec<usize>>,
+}
+
+impl<'a> Refinement<'a> {
+    fn new(problem: &'a Problem, plan: &Plan) -> Self {
+        let n_guests = problem.relations().len();
+        let units = problem.units();
+
+        let mut unit_of = vec![0; n_guests];
+        for (id, unit) in units.iter().enumerate() {
+            for guest in unit {
+                unit_of[*guest] = id;
+            }
+        }
+        let pinned = units
+            .iter()
+            .map(|unit| {
+                unit.iter()
+                    .any(|guest| problem.pinned_table(*guest).is_some())
+            })
+            .collect();
+
+        let mut table_of = vec![0; n_guests];
+        for (table, guests) in plan.iter().enumerate() {
+            for guest in guests {
+                table_of[*guest] = table;
+            }
+        }
+
+        let mut apart_from = vec![Vec::new(); n_guests];
+        for &(i, j) in problem.apart() {
+            apart_from[i].push(j);
+            apart_from[j].push(i);
+        }
+
+        Self {
+            problem,
+            units,
+            unit_of,
+            pinned,
+            table_of,
+            apart_from,
+        }
+    }
+
+    // Makes a pass between the two tables, and returns what it gained.
+    fn pass(&mut self, plan: &mut Plan, table1: usize, table2: usize) -> Gain {
+        let mut locked = vec![false; self.units.len()];
+        let mut history = Vec::new();
+        let mut gain = Gain::default();
+        let mut best_gain = Gain::default();
+        let mut best_length = 0;
+
+        while let Some((change, change_gain)) = self.best_move(plan, table1, table2, &locked) {
+            match change {
+                KlMove::Swap(unit1, unit2) => {
+                    locked[unit1] = true;
+                    locked[unit2] = true;
+                }
+                KlMove::Relocation { unit, .. } => locked[unit] = true,
+            }
+            self.make_move(plan, change);
+            history.push(change);
+
+            gain += change_gain;
+            if gain > best_gain {
+                best_gain = gain;
+                best_length = history.len();
+            }
+        }
+
+        // Go back to the best point in the pass.
+        for change in history.drain(best_length..).rev() {
+            self.undo_move(plan, change);
+        }
+        best_gain
+    }
+
+    // The move between the two tables that gains the most, only moving
+    // units that aren't locked.
+    fn best_move(
+        &self,
+        plan: &Plan,
+        table1: usize,
+        table2: usize,
+        locked: &[bool],
+    ) -> Option<(KlMove, Gain)> {
+        let units1 = self.free_units(plan, table1, locked);
+        let units2 = self.free_units(plan, table2, locked);
+
+        let swaps = units1.iter().flat_map(|unit1| {
+            units2
+                .iter()
+                .filter(move |unit2| self.can_swap(plan, *unit1, **unit2))
+                .map(move |unit2| {
+                    let change = KlMove::Swap(*unit1, *unit2);
+                    (change, self.gain(plan, change))
+                })
+        });
+        let relocations = units1
+            .iter()
+            .map(|unit| (*unit, table1, table2))
+            .chain(units2.iter().map(|unit| (*unit, table2, table1)))
+            .filter(|(unit, from, to)| self.can_relocate(plan, *unit, *from, *to))
+            .map(|(unit, from, to)| {
+                let change = KlMove::Relocation { unit, from, to };
+                (change, self.gain(plan, change))
+            });
+
+        swaps.chain(relocations).max_by_key(|(_, gain)| *gain)
+    }
+
+    // The units at the table that can still be moved.
+    fn free_units(&self, plan: &Plan, table: usize, locked: &[bool]) -> Vec<usize> {
+        plan[table]
+            .iter()
+            .map(|guest| self.unit_of[*guest])
+            .filter(|unit| !locked[*unit] && !self.pinned[*unit])
+            .fold(Vec::new(), |mut units, unit| {
+                if !units.contains(&unit) {
+                    units.push(unit);
+                }
+                units
+            })
+    }
+
+    fn can_swap(&self, plan: &Plan, unit1: usize, unit2: usize) -> bool {
+        let table1 = self.table_of[self.units[unit1][0]];
+        let table2 = self.table_of[self.units[unit2][0]];
+        let (size1, size2) = (self.units[unit1].len(), self.units[unit2].len());
+        self.fits(table1, plan[table1].len() + size2 - size1)
+            && self.fits(table2, plan[table2].len() + size1 - size2)
+            && self.can_join(plan, unit1, table2, Some(unit2))
+            && self.can_join(plan, unit2, table1, Some(unit1))
+    }
+
+    fn can_relocate(&self, plan: &Plan, unit: usize, from: usize, to: usize) -> bool {
+        let size = self.units[unit].len();
+        self.fits(from, plan[from].len() - size)
+            && self.fits(to, plan[to].len() + size)
+            && self.can_join(plan, unit, to, None)
+    }
+
+    // Whether the table could seat this many guests.
+    fn fits(&self, table: usize, n_guests: usize) -> bool {
+        n_guests >= self.problem.min_occupancy && n_guests <= self.problem.capacity(table)
+    }
+
+    // Whether the unit may sit at the table, once the unit leaving it
+    // (if any) has gone. Nobody in the unit may lose their last friend,
+    // though guests who have none yet can go anywhere.
+    fn can_join(&self, plan: &Plan, unit: usize, table: usize, leaving: Option<usize>) -> bool {
+        let joined: Vec<usize> = self.after_move(&plan[table], leaving, Some(unit));
+        self.units[unit].iter().all(|guest| {
+            self.problem.allows(*guest, table)
+                && self.apart_from[*guest].iter().all(|other| {
+                    self.table_of[*other] != table || Some(self.unit_of[*other]) == leaving
+                })
+                && (!self.problem.require_friend
+                    || self.has_friend(*guest, &joined)
+                    || !self.has_friend(*guest, &plan[self.table_of[*guest]]))
+        })
+    }
+
+    // The guests at a table after one unit leaves it and another joins.
+    fn after_move(
+        &self,
+        guests: &[usize],
+        leaving: Option<usize>,
+        joining: Option<usize>,
+    ) -> Vec<usize> {
+        guests
+            .iter()
+            .filter(|guest| Some(self.unit_of[**guest]) != leaving)
+            .chain(joining.into_iter().flat_map(|unit| &self.units[unit]))
+            .copied()
+            .collect()
+    }
+
+    // Whether the guest has a friend among the guests, or likes nobody.
+    fn has_friend(&self, guest: usize, guests: &[usize]) -> bool {
+        !self.problem.likes_anyone(guest)
+            || guests
+                .iter()
+                .any(|other| self.problem.relations().relationship(guest, *other) > 0)
+    }
+
+    // How many hard constraints are broken among the guests at a table.
+    // Pins and forbidden tables are never broken, since `can_join` keeps
+    // to them.
+    fn violations(&self, guests: &[usize]) -> i64 {
+        let apart = guests
+            .iter()
+            .flat_map(|guest| {
+                self.apart_from[*guest]
+                    .iter()
+                    .map(move |other| (*guest, *other))
+            })
+            .filter(|(guest, other)| guest < other && guests.contains(other))
+            .count();
+        let friendless = if self.problem.require_friend {
+            guests
+                .iter()
+                .filter(|guest| !self.has_friend(**guest, guests))
+                .count()
+        } else {
+            0
+        };
+        (apart + friendless) as i64
+    }
+
+    fn gain(&self, plan: &Plan, change: KlMove) -> Gain {
+        Gain {
+            repaired: self.repaired(plan, change),
+            happiness: self.happiness_gain(plan, change),
+        }
+    }
+
+    // How many fewer hard constraints the move leaves broken.
+    fn repaired(&self, plan: &Plan, change: KlMove) -> i64 {
+        if self.problem.apart().is_empty() && !self.problem.require_friend {
+            return 0;
+        }
+        let (table1, table2, unit1, unit2) = match change {
+            KlMove::Swap(unit1, unit2) => (
+                self.table_of[self.units[unit1][0]],
+                self.table_of[self.units[unit2][0]],
+                Some(unit1),
+                Some(unit2),
+            ),
+            KlMove::Relocation { unit, from, to } => (from, to, Some(unit), None),
+        };
+        let before = self.violations(&plan[table1]) + self.violations(&plan[table2]);
+        let after = self.violations(&self.after_move(&plan[table1], unit1, unit2))
+            + self.violations(&self.after_move(&plan[table2], unit2, unit1));
+        before - after
+    }
+
+    // How much total happiness the move adds.
+    fn happiness_gain(&self, plan: &Plan, change: KlMove) -> i64 {
+        let gain = match change {
+            KlMove::Swap(unit1, unit2) => {
+                let table1 = self.table_of[self.units[unit1][0]];
+                let table2 = self.table_of[self.units[unit2][0]];
+                // The two units don't sit together before or after.
+                let between = 2 * self.affinity(unit1, &self.units[unit2]);
+                self.affinity(unit1, &plan[table2]) - self.affinity(unit1, &plan[table1])
+                    + self.affinity(unit2, &plan[table1])
+                    - self.affinity(unit2, &plan[table2])
+                    - between
+            }
+            KlMove::Relocation { unit, from, to } => {
+                self.affinity(unit, &plan[to]) - self.affinity(unit, &plan[from])
+            }
+        };
+        // Relationships are symmetric, so each one counts twice.
+        2 * gain
+    }
+
+    // How much the unit likes the guests, apart from its own members.
+    fn affinity(&self, unit: usize, guests: &[usize]) -> i64 {
+        let relations = self.problem.relations();
+        self.units[unit]
+            .iter()
+            .flat_map(|guest| guests.iter().map(move |other| (*guest, *other)))
+            .filter(|(_, other)| self.unit_of[*other] != unit)
+            .map(|(guest, other)| relations.relationship(guest, other))
+            .sum()
+    }
+
+    fn make_move(&mut self, plan: &mut Plan, change: KlMove) {
+        match change {
+            KlMove::Swap(unit1, unit2) => {
+                let table1 = self.table_of[self.units[unit1][0]];
+                let table2 = self.table_of[self.units[unit2][0]];
+                self.move_unit(plan, unit1, table2);
+                self.move_unit(plan, unit2, table1);
+            }
+            KlMove::Relocation { unit, to, .. } => self.move_unit(plan, unit, to),
+        }
+    }
+
+    fn undo_move(&mut self, plan: &mut Plan, change: KlMove) {
+        match change {
+            // Swapping them again puts them back.
+            KlMove::Swap(..) => self.make_move(plan, change),
+            KlMove::Relocation { unit, from, .. } => self.move_unit(plan, unit, from),
+        }
+    }
+
+    fn move_unit(&mut self, plan: &mut Plan, unit: usize, to: usize) {
+        let from = self.table_of[self.units[unit][0]];
+        plan[from].retain(|guest| self.unit_of[*guest] != unit);
+        for guest in &self.units[unit] {
+            plan[to].push(*guest);
+            self.table_of[*guest] = to;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::metrics::Metrics;
+    use crate::test_util::{
+        assert_feasible, assert_improves_on_start, constrained_wedding, random_wedding,
+    };
+    use crate::{LahcPlanner, WarmStart};
+
+    type Float = ordered_float::NotNan<f64>;
+
+    #[test]
+    fn kernighan_lin_never_worse_than_start() {
+        for seed in 0..5 {
+            assert_improves_on_start(seed, 30, 5, KernighanLinPlanner::new);
+        }
+    }
+
+    #[test]
+    fn kernighan_lin_respects_constraints() {
+        let problem = constrained_wedding(0).with_min_occupancy(3).unwrap();
+        let friendly = problem.clone().with_friend_required();
+        // Some of these start out breaking constraints.
+        for seed in 0..100 {
+            for problem in &[&problem, &friendly] {
+                let plan = KernighanLinPlanner::new(StdRng::seed_from_u64(seed))
+                    .plan(problem)
+                    .unwrap();
+                assert_feasible(&plan, problem);
+            }
+        }
+    }
+
+    #[test]
+    fn kernighan_lin_polishes_other_plans() {
+        let problem = random_wedding(&mut StdRng::seed_from_u64(1), 40, 5);
+        let rng = StdRng::seed_from_u64(1);
+        let threshold = Float::new(0.01).unwrap();
+
+        let lahc = LahcPlanner::new(rng.clone(), threshold)
+            .plan(&problem)
+            .unwrap();
+        let mut planner = WarmStart {
+            start: LahcPlanner::new(rng, threshold),
+            search: KernighanLinPlanner::new(StdRng::seed_from_u64(2)),
+        };
+        let polished = planner.plan(&problem).unwrap();
+        assert!(
+            Metrics::new(&polished, &problem).total_happiness()
+                >= Metrics::new(&lahc, &problem).total_happiness()
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+/// How the seats around a table are arranged.
+/// Seats are numbered from 0, and guests fill them in the order they
+/// are listed in a plan, so any empty seats are at the end.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableShape {
+    /// Seats go round in a circle, so the last seat is next to the first.
+    Round,
+    /// With `m = ceil(n / 2)`, seats `0..m` go along one side and the rest
+    /// go along the other, with seat `i` opposite seat `i + m`.
+    Rectangular,
+    /// A single row of seats, like a head table.
+    Banquet,
+}
+
+/// Where two guests at the same table sit relative to each other.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum SeatPosition {
+    Neighbour,
+    Across,
+    SameTable,
+}
+
+/// How much a relationship counts, depending on where the two guests sit.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdjacencyWeights {
+    pub neighbour: i64,
+    pub across: i64,
+    pub same_table: i64,
+}
+
+/// The shape of every table, and how much sitting close to someone matters.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    // Either one shape per table, or a single shape for all of them.
+    pub shapes: Vec<TableShape>,
+    pub weights: AdjacencyWeights,
+}
+
+impl TableShape {
+    /// Returns where the two (different) seats are relative to each other
+    /// at a table with `capacity` seats.
+    pub fn position(self, capacity: usize, seat1: usize, seat2: usize) -> SeatPosition {
+        let distance = seat1.abs_diff(seat2);
+
+        match self {
+            TableShape::Round => {
+                let distance = distance.min(capacity - distance);
+                if distance == 1 {
+                    SeatPosition::Neighbour
+                } else if distance == capacity / 2 {
+                    SeatPosition::Across
+                } else {
+                    SeatPosition::SameTable
+                }
+            }
+            TableShape::Rectangular => {
+                let side_len = capacity.div_ceil(2);
+                let same_side = (seat1 < side_len) == (seat2 < side_len);
+                if same_side && distance == 1 {
+                    SeatPosition::Neighbour
+                } else if !same_side && distance == side_len {
+                    SeatPosition::Across
+                } else {
+                    SeatPosition::SameTable
+                }
+            }
+            TableShape::Banquet => {
+                if distance == 1 {
+                    SeatPosition::Neighbour
+                } else {
+                    SeatPosition::SameTable
+                }
+            }
+        }
+    }
+}
+
+impl Layout {
+    /// Returns the shape of the table.
+    pub fn shape(&self, table: usize) -> TableShape {
+        if self.shapes.len() == 1 {
+            self.shapes[0]
+        } else {
+            self.shapes[table]
+        }
+    }
+
+    /// Returns how much a relationship counts between guests in the two
+    /// seats of the table.
+    pub fn weight(&self, table: usize, capacity: usize, seat1: usize, seat2: usize) -> i64 {
+        match self.shape(table).position(capacity, seat1, seat2) {
+            SeatPosition::Neighbour => self.weights.neighbour,
+            SeatPosition::Across => self.weights.across,
+            SeatPosition::SameTable => self.weights.same_table,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_positions() {
+        let round = TableShape::Round;
+        assert_eq!(round.position(8, 0, 1), SeatPosition::Neighbour);
+        assert_eq!(round.position(8, 7, 0), SeatPosition::Neighbour);
+        assert_eq!(round.position(8, 2, 6), SeatPosition::Across);
+        assert_eq!(round.position(8, 2, 5), SeatPosition::SameTable);
+
+        // Odd tables have two seats opposite.
+        assert_eq!(round.position(7, 0, 3), SeatPosition::Across);
+        assert_eq!(round.position(7, 0, 4), SeatPosition::Across);
+    }
+
+    #[test]
+    fn rectangular_positions() {
+        // Seats 0, 1, 2 face seats 3, 4, 5.
+        let rectangle = TableShape::Rectangular;
+        assert_eq!(rectangle.position(6, 0, 1), SeatPosition::Neighbour);
+        assert_eq!(rectangle.position(6, 2, 3), SeatPosition::SameTable);
+        assert_eq!(rectangle.position(6, 1, 4), SeatPosition::Across);
+        assert_eq!(rectangle.position(6, 0, 5), SeatPosition::SameTable);
+        assert_eq!(rectangle.position(6, 4, 5), SeatPosition::Neighbour);
+    }
+
+    #[test]
+    fn banquet_positions() {
+        let row = TableShape::Banquet;
+        assert_eq!(row.position(10, 4, 5), SeatPosition::Neighbour);
+        assert_eq!(row.position(10, 0, 9), SeatPosition::SameTable);
+    }
+}
@@ -1,63 +1,190 @@
 mod hill_climb;
 
-pub use hill_climb::{HillClimbingPlanner, LahcPlanner};
+pub use hill_climb::{ClimbMode, HillClimbingPlanner, LahcPlanner};
+
+mod moves;
+
+pub use moves::{GuestSampling, MoveKind, MoveMix};
+
+mod anneal;
+
+pub use anneal::{CoolingSchedule, SimulatedAnnealingPlanner};
+
+mod tabu;
+
+pub use tabu::TabuSearchPlanner;
+
+mod genetic;
+
+pub use genetic::GeneticPlanner;
+
+mod greedy;
+
+pub use greedy::{GreedyPlanner, Insertion};
+
+mod kernighan_lin;
+
+pub use kernighan_lin::KernighanLinPlanner;
+
+mod multilevel;
+
+pub use multilevel::MultilevelPlanner;
+
+mod lns;
+
+pub use lns::{Destroy, LnsPlanner, Repair};
+
+mod branch_bound;
+
+pub use branch_bound::{BranchAndBoundPlanner, Solution};
+
+mod brute_force;
+
+pub use brute_force::{BruteForcePlanner, MAX_BRUTE_FORCE_GUESTS};
+
+mod subset_dp;
+
+pub use subset_dp::{SubsetDpPlanner, DEFAULT_DP_MEMORY_LIMIT};
+
+mod relations;
+
+pub use relations::{GuestRelations, RelationsError, Symmetrize};
+
+mod layout;
+
+pub use layout::{AdjacencyWeights, Layout, SeatPosition, TableShape};
+
+mod problem;
+
+pub use problem::{Problem, ProblemError};
+
+mod evaluator;
 
 pub mod metrics;
 
-use serde::{Deserialize, Serialize};
+pub mod objective;
 
-/// A complete, undirected graph that models the relationship between
-/// all guests at a wedding.
-/// Guests are indexed as `usize`.
-#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
-pub struct GuestRelations {
-    // A square array, symmetrical (ie `relationships[i][j] == relationships[j][i]`)
-    // with zeros along the diagonal.
-    relationships: Vec<Vec<i64>>,
-}
+#[cfg(test)]
+mod test_util;
 
-impl GuestRelations {
-    pub fn new(relationships: Vec<Vec<i64>>) -> Self {
-        // TODO: check/force symmetry, squareness, and lack of self-loops.
-        Self::new_unchecked(relationships)
-    }
+use std::convert::TryFrom;
+use std::fmt;
+
+use anyhow::Context;
+
+pub type Plan = Vec<Vec<usize>>;
 
-    pub fn new_unchecked(relationships: Vec<Vec<i64>>) -> Self {
-        Self { relationships }
+pub trait SeatingPlanner {
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError>;
+
+    /// Builds a plan for a local search to start from, which may break
+    /// hard constraints that the search can repair. Defaults to `plan`.
+    fn construct(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        self.plan(problem)
     }
+}
 
-    /// Returns the degree of friendship two guests have.
-    /// Positive is good, negative is bad. 0 is either unmet or self.
-    /// # Panics
-    /// Panics if either guest is unknown (out of bounds).
-    pub fn relationship(&self, guest1: usize, guest2: usize) -> i64 {
-        self.relationships[guest1][guest2]
+// Lets a planner be run without giving it up.
+impl<T> SeatingPlanner for &mut T
+where
+    T: SeatingPlanner + ?Sized,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        (**self).plan(problem)
     }
 
-    /// Returns the number of guests.
-    pub fn len(&self) -> usize {
-        self.relationships.len()
+    fn construct(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        (**self).construct(problem)
     }
+}
 
-    /// Returns an iterator over the relationships. Should be combined with `.enumerate()`
-    /// if you want the indicies of the relevant guests.
-    pub fn iter(&self) -> impl Iterator<Item = impl Iterator<Item = i64> + '_> + '_ {
-        self.relationships.iter().map(|row| row.iter().copied())
+/// Planners that work by improving a plan, which don't have to start
+/// from a random one.
+pub trait LocalSearch {
+    /// Improves on a plan, which must seat every guest at a table that
+    /// has room for them. Returns `PlanError::SplitGroup` if the plan
+    /// separates guests who must sit together, as the planners only
+    /// ever move them as a group.
+    fn improve(&mut self, problem: &Problem, plan: Plan) -> Result<Plan, PlanError>;
+}
+
+/// Improves on the plan of one planner with another, such as starting
+/// a hill climber from a greedy plan.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct WarmStart<S, L> {
+    pub start: S,
+    pub search: L,
+}
+
+impl<S, L> SeatingPlanner for WarmStart<S, L>
+where
+    S: SeatingPlanner,
+    L: LocalSearch,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = self.start.construct(problem)?;
+        self.search.improve(problem, plan)
     }
 }
 
-pub type Plan = Vec<Vec<usize>>;
+/// Runs a local search from a random plan, or from a greedy one if
+/// `greedy_start` is set.
+pub fn run_local_search<L>(search: L, greedy_start: bool) -> anyhow::Result<()>
+where
+    L: SeatingPlanner + LocalSearch,
+{
+    if greedy_start {
+        run(WarmStart {
+            start: GreedyPlanner::default(),
+            search,
+        })
+    } else {
+        run(search)
+    }
+}
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Problem {
-    pub relations: GuestRelations,
-    pub n_tables: usize,
+/// The ways in which a planner can fail to produce an acceptable plan.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum PlanError {
+    /// The groups of guests who must sit together couldn't be fitted
+    /// around the tables.
+    Unpackable,
+    /// The best plan found still breaks some hard constraints.
+    Infeasible { n_violations: usize },
+    /// The search ended without finding any plan that keeps every
+    /// hard constraint.
+    NotFound,
+    /// The planner can't handle this kind of problem.
+    Unsupported(&'static str),
+    /// The plan to improve on doesn't seat the guests who must sit
+    /// together at the same table.
+    SplitGroup,
 }
 
-pub trait SeatingPlanner {
-    fn plan(&mut self, problem: &Problem) -> Plan;
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::Unpackable => write!(
+                f,
+                "could not fit the guests who must sit together around the tables"
+            ),
+            PlanError::Infeasible { n_violations } => write!(
+                f,
+                "the best plan found breaks {} hard constraints",
+                n_violations
+            ),
+            PlanError::NotFound => write!(f, "no plan keeping every hard constraint was found"),
+            PlanError::Unsupported(reason) => write!(f, "unsupported problem: {}", reason),
+            PlanError::SplitGroup => write!(
+                f,
+                "the starting plan splits up guests who must sit together"
+            ),
+        }
+    }
 }
 
+impl std::error::Error for PlanError {}
+
 pub fn run<T>(mut planner: T) -> anyhow::Result<()>
 where
     T: SeatingPlanner,
@@ -70,8 +197,12 @@ where
     let stdout = io::stdout();
     let writer = stdout.lock();
 
-    let problem: Problem = serde_json::from_reader(reader)?;
-    let plan = planner.plan(&problem);
+    // Parse and validate separately, so an impossible problem is reported
+    // as a `ProblemError` rather than a parsing error.
+    let unchecked: problem::UncheckedProblem =
+        serde_json::from_reader(reader).context("Could not parse problem.")?;
+    let problem = Problem::try_from(unchecked).context("Invalid problem.")?;
+    let plan = planner.plan(&problem).context("Could not plan seating.")?;
 
     serde_json::to_writer(writer, &plan)?;
 
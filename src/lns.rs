@@ -0,0 +1,472 @@
+use crate::hill_climb::{check_feasible, check_groups, fitness, random_plan};
+use crate::metrics::Metrics;
+use crate::objective::{Objective, TotalHappiness};
+use crate::{LocalSearch, Plan, PlanError, Problem, SeatingPlanner};
+
+use std::cmp::Reverse;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+
+// How many guests are unseated each step, unless told otherwise.
+const DEFAULT_DESTROY_SIZE: usize = 8;
+
+// How many steps without finding a better plan before we give up,
+// unless told otherwise.
+const DEFAULT_PATIENCE: usize = 200;
+
+// Exact repair tries every way of re-seating the groups, so with more than
+// this many we re-seat them greedily instead.
+const MAX_EXACT_REPAIR_UNITS: usize = 8;
+
+// How a destroy operator is rewarded for finding a new best plan, or a
+// different plan that's just as good.
+const NEW_BEST_SCORE: f64 = 3.0;
+const ACCEPTED_SCORE: f64 = 1.0;
+
+// How many steps go by between updates to the operator weights, how far
+// each update moves them towards the latest scores, and the lowest they go.
+const SEGMENT_LENGTH: usize = 20;
+const REACTION_FACTOR: f64 = 0.2;
+const MIN_WEIGHT: f64 = 0.1;
+
+/// How a step of large neighbourhood search chooses who to unseat.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Destroy {
+    /// Guests chosen at random.
+    Random,
+    /// Guests chosen from among the least happy.
+    Unhappiest,
+    /// Everyone at two random tables.
+    TwoTables,
+}
+
+impl Destroy {
+    const ALL: [Destroy; 3] = [Destroy::Random, Destroy::Unhappiest, Destroy::TwoTables];
+}
+
+/// How a step of large neighbourhood search re-seats the guests it unseated.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub enum Repair {
+    /// Seat each group where they add the most happiness, in turn.
+    #[default]
+    Greedy,
+    /// Try every way of seating them, if there aren't too many.
+    Exact,
+}
+
+impl FromStr for Repair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "greedy" => Ok(Repair::Greedy),
+            "exact" => Ok(Repair::Exact),
+            _ => Err(anyhow!("Unrecognised repair operator")),
+        }
+    }
+}
+
+/// Adaptive large neighbourhood search: each step unseats some guests and
+/// re-seats them, keeping the new plan if it's no worse. Destroy operators
+/// that have found better plans recently are picked more often.
+///
+/// Pinned guests are never unseated. Re-seating maximises total happiness,
+/// ignoring where at the table people sit, but plans are compared with
+/// the objective.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LnsPlanner<R, O = TotalHappiness> {
+    rng: R,
+    objective: O,
+    repair: Repair,
+    // How many guests to unseat each step, except when clearing tables.
+    destroy_size: usize,
+    // How many steps without a new best plan before we stop.
+    patience: usize,
+}
+
+impl<R> LnsPlanner<R>
+where
+    R: Rng,
+{
+    pub fn new(rng: R) -> Self {
+        Self::with_objective(rng, TotalHappiness)
+    }
+}
+
+impl<R, O> LnsPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    pub fn with_objective(rng: R, objective: O) -> Self {
+        Self {
+            rng,
+            objective,
+            repair: Repair::default(),
+            destroy_size: DEFAULT_DESTROY_SIZE,
+            patience: DEFAULT_PATIENCE,
+        }
+    }
+
+    pub fn with_repair(self, repair: Repair) -> Self {
+        Self { repair, ..self }
+    }
+
+    /// Sets roughly how many guests are unseated each step. Groups who
+    /// must sit together are unseated whole, so it can be a few more.
+    pub fn with_destroy_size(self, destroy_size: NonZeroUsize) -> Self {
+        Self {
+            destroy_size: destroy_size.get(),
+            ..self
+        }
+    }
+
+    /// Sets how many steps without finding a better plan we allow
+    /// before stopping.
+    pub fn with_patience(self, patience: usize) -> Self {
+        Self { patience, ..self }
+    }
+}
+
+impl<R, O> SeatingPlanner for LnsPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
+}
+
+impl<R, O> LocalSearch for LnsPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        let search = Search::new(problem);
+        let mut current_fitness = fitness(&self.objective, &Metrics::new(&plan, problem));
+
+        let mut weights = [1.0; Destroy::ALL.len()];
+        let mut scores = [0.0; Destroy::ALL.len()];
+        let mut uses = [0; Destroy::ALL.len()];
+
+        let mut step = 0;
+        let mut since_best = 0;
+        while since_best < self.patience {
+            let choice = WeightedIndex::new(weights)
+                .expect("The weights are positive.")
+                .sample(&mut self.rng);
+            uses[choice] += 1;
+
+            let mut new_plan = plan.clone();
+            let unseated = search.destroy(
+                &mut self.rng,
+                &mut new_plan,
+                Destroy::ALL[choice],
+                self.destroy_size,
+            );
+            let repaired = match self.repair {
+                Repair::Exact if unseated.len() <= MAX_EXACT_REPAIR_UNITS => {
+                    search.repair_exactly(&mut new_plan, &unseated)
+                }
+                _ => search.repair_greedily(&mut new_plan, &unseated),
+            };
+
+            since_best += 1;
+            if repaired {
+                let new_fitness = fitness(&self.objective, &Metrics::new(&new_plan, problem));
+                if new_fitness > current_fitness {
+                    scores[choice] += NEW_BEST_SCORE;
+                    since_best = 0;
+                } else if new_fitness == current_fitness && new_plan != plan {
+                    scores[choice] += ACCEPTED_SCORE;
+                }
+                if new_fitness >= current_fitness {
+                    current_fitness = new_fitness;
+                    plan = new_plan;
+                }
+            }
+
+            step += 1;
+            if step % SEGMENT_LENGTH == 0 {
+                for ((weight, score), n_uses) in weights.iter_mut().zip(&mut scores).zip(&mut uses)
+                {
+                    if *n_uses > 0 {
+                        let mean_score = *score / *n_uses as f64;
+                        *weight = ((1.0 - REACTION_FACTOR) * *weight
+                            + REACTION_FACTOR * mean_score)
+                            .max(MIN_WEIGHT);
+                    }
+                    *score = 0.0;
+                    *n_uses = 0;
+                }
+            }
+        }
+
+        check_feasible(plan, problem)
+    }
+}
+
+struct Search<'a> {
+    problem: &'a Problem,
+    // The groups who must sit together.
+    units: Vec<Vec<usize>>,
+    // Index by guest to get their unit.
+    unit_of: Vec<usize>,
+    // Index by unit to get whether anyone in it is pinned in place.
+    pinned: Vec<bool>,
+    apart_from: Vec<Vec<usize>>,
+}
+
+impl<'a> Search<'a> {
+    fn new(problem: &'a Problem) -> Self {
+        let n_guests = problem.relations().len();
+        let units = problem.units();
+
+        let mut unit_of = vec![0; n_guests];
+        for (id, unit) in units.iter().enumerate() {
+            for guest in unit {
+                unit_of[*guest] = id;
+            }
+        }
+        let pinned = units
+            .iter()
+            .map(|unit| {
+                unit.iter()
+                    .any(|guest| problem.pinned_table(*guest).is_some())
+            })
+            .collect();
+
+        let mut apart_from = vec![Vec::new(); n_guests];
+        for &(i, j) in problem.apart() {
+            apart_from[i].push(j);
+            apart_from[j].push(i);
+        }
+
+        Self {
+            problem,
+            units,
+            unit_of,
+            pinned,
+            apart_from,
+        }
+    }
+
+    // Unseats some units, and returns them.
+    fn destroy<R: Rng>(
+        &self,
+        mut rng: R,
+        plan: &mut Plan,
+        destroy: Destroy,
+        size: usize,
+    ) -> Vec<usize> {
+        let movable = |guest: &usize| !self.pinned[self.unit_of[*guest]];
+        let mut guests: Vec<usize> = match destroy {
+            Destroy::Random => {
+                let mut guests: Vec<usize> =
+                    plan.iter().flatten().copied().filter(movable).collect();
+                guests.shuffle(&mut rng);
+                guests
+            }
+            Destroy::Unhappiest => {
+                // Pick at random from among the unhappiest, so that a step
+                // that fails isn't tried again and again.
+                let metrics = Metrics::new(plan, self.problem);
+                let happinesses: Vec<i64> = metrics.happinesses().collect();
+                let mut guests: Vec<usize> =
+                    plan.iter().flatten().copied().filter(movable).collect();
+                guests.sort_by_key(|guest| happinesses[*guest]);
+                guests.truncate(2 * size);
+                guests.shuffle(&mut rng);
+                guests
+            }
+            Destroy::TwoTables => {
+                let tables = rand::seq::index::sample(&mut rng, plan.len(), plan.len().min(2));
+                tables
+                    .iter()
+                    .flat_map(|table| plan[table].iter().copied())
+                    .filter(movable)
+                    .collect()
+            }
+        };
+        if destroy != Destroy::TwoTables {
+            guests.truncate(size);
+        }
+
+        let mut unseated: Vec<usize> = guests.iter().map(|guest| self.unit_of[*guest]).collect();
+        unseated.sort_unstable();
+        unseated.dedup();
+        for table in plan.iter_mut() {
+            table.retain(|guest| unseated.binary_search(&self.unit_of[*guest]).is_err());
+        }
+        unseated
+    }
+
+    // Whether the unit may join the table as it is now.
+    fn can_seat(&self, plan: &Plan, unit: usize, table: usize) -> bool {
+        let unit = &self.units[unit];
+        plan[table].len() + unit.len() <= self.problem.capacity(table)
+            && unit.iter().all(|guest| {
+                self.problem.allows(*guest, table)
+                    && !self.apart_from[*guest]
+                        .iter()
+                        .any(|other| plan[table].contains(other))
+            })
+    }
+
+    // How much total happiness the unit adds by joining the table.
+    fn gain(&self, plan: &Plan, unit: usize, table: usize) -> i64 {
+        let relations = self.problem.relations();
+        let unit = &self.units[unit];
+        let with_table: i64 = unit
+            .iter()
+            .flat_map(|guest| plan[table].iter().map(move |other| (*guest, *other)))
+            .map(|(guest, other)| relations.relationship(guest, other))
+            .sum();
+        2 * with_table
+    }
+
+    fn seat(&self, plan: &mut Plan, unit: usize, table: usize) {
+        plan[table].extend_from_slice(&self.units[unit]);
+    }
+
+    fn unseat(&self, plan: &mut Plan, unit: usize, table: usize) {
+        let size = self.units[unit].len();
+        let len = plan[table].len();
+        plan[table].truncate(len - size);
+    }
+
+    fn reaches_min_occupancy(&self, plan: &Plan) -> bool {
+        plan.iter()
+            .all(|table| table.len() >= self.problem.min_occupancy)
+    }
+
+    // Seats the biggest units first, each where they add the most happiness,
+    // preferring tables below their minimum occupancy.
+    // Returns `false` if someone couldn't be seated.
+    fn repair_greedily(&self, plan: &mut Plan, unseated: &[usize]) -> bool {
+        let mut unseated = unseated.to_vec();
+        unseated.sort_by_key(|unit| Reverse(self.units[*unit].len()));
+
+        for unit in unseated {
+            let best = (0..plan.len())
+                .filter(|table| self.can_seat(plan, unit, *table))
+                .max_by_key(|table| {
+                    let needs_guests = plan[*table].len() < self.problem.min_occupancy;
+                    (needs_guests, self.gain(plan, unit, *table))
+                });
+            match best {
+                Some(table) => self.seat(plan, unit, table),
+                None => return false,
+            }
+        }
+        self.reaches_min_occupancy(plan)
+    }
+
+    // Tries every way of seating the units, and keeps the happiest.
+    // Returns `false` if there was no way to seat them.
+    fn repair_exactly(&self, plan: &mut Plan, unseated: &[usize]) -> bool {
+        let mut best = None;
+        self.seat_exactly(plan, unseated, 0, &mut Vec::new(), &mut best);
+        match best {
+            Some((_, tables)) => {
+                for (unit, table) in unseated.iter().zip(tables) {
+                    self.seat(plan, *unit, table);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Seats the rest of the units in every way. `tables` holds where each
+    // unit before them was seated, and `best` the best total gain so far
+    // and where it seated everyone.
+    fn seat_exactly(
+        &self,
+        plan: &mut Plan,
+        unseated: &[usize],
+        gain: i64,
+        tables: &mut Vec<usize>,
+        best: &mut Option<(i64, Vec<usize>)>,
+    ) {
+        let (unit, rest) = match unseated.split_first() {
+            Some(split) => split,
+            None => {
+                if self.reaches_min_occupancy(plan) && best.as_ref().is_none_or(|(b, _)| gain > *b)
+                {
+                    *best = Some((gain, tables.clone()));
+                }
+                return;
+            }
+        };
+
+        for table in 0..plan.len() {
+            if !self.can_seat(plan, *unit, table) {
+                continue;
+            }
+            let unit_gain = self.gain(plan, *unit, table);
+            self.seat(plan, *unit, table);
+            tables.push(table);
+            self.seat_exactly(plan, rest, gain + unit_gain, tables, best);
+            tables.pop();
+            self.unseat(plan, *unit, table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{
+        assert_feasible, assert_improves_on_start, constrained_wedding, random_wedding,
+    };
+    use crate::BruteForcePlanner;
+
+    #[test]
+    fn exact_repair_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..5 {
+            let problem = random_wedding(&mut rng, 8, 3);
+            let search = Search::new(&problem);
+            let mut plan = vec![Vec::new(); 3];
+            let everyone: Vec<usize> = (0..8).collect();
+            assert!(search.repair_exactly(&mut plan, &everyone));
+
+            let optimum = BruteForcePlanner::new().plan(&problem).unwrap();
+            assert_eq!(
+                Metrics::new(&plan, &problem).total_happiness(),
+                Metrics::new(&optimum, &problem).total_happiness()
+            );
+        }
+    }
+
+    #[test]
+    fn lns_never_worse_than_start() {
+        for (seed, repair) in [Repair::Greedy, Repair::Exact].iter().enumerate() {
+            assert_improves_on_start(seed as u64, 40, 5, |rng| {
+                LnsPlanner::new(rng).with_repair(*repair)
+            });
+        }
+    }
+
+    #[test]
+    fn lns_respects_constraints() {
+        let problem = constrained_wedding(0).with_min_occupancy(4).unwrap();
+        for (seed, repair) in [Repair::Greedy, Repair::Exact].iter().enumerate() {
+            let plan = LnsPlanner::new(StdRng::seed_from_u64(seed as u64))
+                .with_repair(*repair)
+                .plan(&problem)
+                .unwrap();
+            assert_feasible(&plan, &problem);
+        }
+    }
+}
@@ -1,31 +1,101 @@
-use crate::{GuestRelations, Plan};
+use crate::{Plan, Problem};
 
 pub struct Metrics {
     // Index by guest to get a vector of how they feel about
-    // everone *else* at the table.
+    // everone *else* at the table, weighted by where they sit.
     neighbour_relationships: Vec<Vec<i64>>,
+    // How many `together` lists are split across tables.
+    together_violations: usize,
+    // How many `apart` pairs share a table.
+    apart_violations: usize,
+    // How many guests are at a table they are pinned away from.
+    pin_violations: usize,
+    // How many guests are without any of their friends, when the
+    // problem requires everyone to sit with a friend.
+    friendless_violations: usize,
 }
 
 impl Metrics {
-    pub fn new(plan: &Plan, relationships: &GuestRelations) -> Self {
+    pub fn new(plan: &Plan, problem: &Problem) -> Self {
+        let relationships = problem.relations();
         let mut inner = vec![Vec::new(); relationships.len()];
 
-        for table in plan {
-            for guest in table {
-                for neighbour in table {
+        for (table_id, table) in plan.iter().enumerate() {
+            for (seat, guest) in table.iter().enumerate() {
+                for (other_seat, neighbour) in table.iter().enumerate() {
                     if guest == neighbour {
                         continue;
                     }
-                    inner[*guest].push(relationships.relationship(*guest, *neighbour))
+                    let weight = problem.weight(table_id, seat, other_seat);
+                    inner[*guest].push(weight * relationships.relationship(*guest, *neighbour))
                 }
             }
         }
 
+        let mut tables = vec![0; relationships.len()];
+        for (table_id, table) in plan.iter().enumerate() {
+            for guest in table {
+                tables[*guest] = table_id;
+            }
+        }
+
+        let together_violations = problem
+            .together()
+            .iter()
+            .filter(|list| list.iter().any(|guest| tables[*guest] != tables[list[0]]))
+            .count();
+        let apart_violations = problem
+            .apart()
+            .iter()
+            .filter(|(i, j)| tables[*i] == tables[*j])
+            .count();
+        let pin_violations = (0..relationships.len())
+            .filter(|guest| !problem.allows(*guest, tables[*guest]))
+            .count();
+        // Weighted by where people sit, the same as for `n_lonely`.
+        let friendless_violations = if problem.require_friend {
+            (0..relationships.len())
+                .filter(|guest| problem.likes_anyone(*guest))
+                .filter(|guest| inner[*guest].iter().all(|r| *r <= 0))
+                .count()
+        } else {
+            0
+        };
+
         Self {
             neighbour_relationships: inner,
+            together_violations,
+            apart_violations,
+            pin_violations,
+            friendless_violations,
         }
     }
 
+    pub fn together_violations(&self) -> usize {
+        self.together_violations
+    }
+
+    pub fn apart_violations(&self) -> usize {
+        self.apart_violations
+    }
+
+    pub fn pin_violations(&self) -> usize {
+        self.pin_violations
+    }
+
+    pub fn friendless_violations(&self) -> usize {
+        self.friendless_violations
+    }
+
+    /// The number of hard constraints the plan breaks. A plan is only
+    /// acceptable if this is zero.
+    pub fn n_violations(&self) -> usize {
+        self.together_violations
+            + self.apart_violations
+            + self.pin_violations
+            + self.friendless_violations
+    }
+
     pub fn n_lonely(&self) -> usize {
         self.neighbour_relationships
             .iter()
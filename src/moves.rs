@@ -0,0 +1,621 @@
+use crate::evaluator::IncrementalEvaluator;
+use crate::hill_climb::{occupied_tables, Chain, Move, Neighbourhood};
+use crate::Problem;
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+
+type Float = ordered_float::NotNan<f64>;
+
+// The most tables an ejection chain passes through.
+const MAX_CHAIN_LENGTH: usize = 5;
+
+/// The kinds of random move the local search planners can make.
+///
+/// This is the only way to choose which moves are made: each kind has its
+/// own generator inside the crate, which works on the planners' private
+/// view of the plan, so new kinds of move are added here.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum MoveKind {
+    /// Swaps two guests at different tables, or moves one into an empty
+    /// seat, along with anyone who must sit with them.
+    Swap,
+    /// Moves one guest each from three tables around in a cycle.
+    Cycle,
+    /// Moves a guest to the table they like most, whose least attached
+    /// guest moves to the table they like most, and so on, until someone
+    /// finds an empty seat or the first guest's seat is filled.
+    EjectionChain,
+}
+
+impl MoveKind {
+    pub const ALL: [MoveKind; 3] = [MoveKind::Swap, MoveKind::Cycle, MoveKind::EjectionChain];
+
+    pub(crate) fn generator(self) -> &'static dyn MoveGenerator {
+        match self {
+            MoveKind::Swap => &InterTableSwaps,
+            MoveKind::Cycle => &ThreeCycles,
+            MoveKind::EjectionChain => &EjectionChains,
+        }
+    }
+}
+
+impl FromStr for MoveKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "swap" | "swaps" => Ok(MoveKind::Swap),
+            "cycle" | "cycles" | "3-cycle" => Ok(MoveKind::Cycle),
+            "chain" | "chains" | "ejection-chain" => Ok(MoveKind::EjectionChain),
+            _ => Err(anyhow!("Unrecognised kind of move")),
+        }
+    }
+}
+
+/// How often each kind of move is tried, relative to the others.
+/// By default, only swaps are tried.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct MoveMix {
+    // Index by `MoveKind as usize`.
+    weights: [Float; 3],
+}
+
+impl MoveMix {
+    /// Only tries moves of this kind.
+    pub fn only(kind: MoveKind) -> Self {
+        let zero = Float::new(0.0).unwrap();
+        Self { weights: [zero; 3] }.with_weight(kind, 1.0)
+    }
+
+    /// Sets how often moves of this kind are tried, relative to the others.
+    /// # Panics
+    /// Panics unless the weight is finite and not negative.
+    pub fn with_weight(mut self, kind: MoveKind, weight: f64) -> Self {
+        assert!(weight.is_finite() && weight >= 0.0);
+        self.weights[kind as usize] = Float::new(weight).unwrap();
+        self
+    }
+
+    pub fn weight(&self, kind: MoveKind) -> f64 {
+        self.weights[kind as usize].into_inner()
+    }
+
+    // Falls back to swaps if every weight is zero.
+    pub(crate) fn choose<R: Rng>(&self, mut rng: R) -> MoveKind {
+        let mut kinds = MoveKind::ALL
+            .iter()
+            .filter(|kind| self.weight(**kind) > 0.0);
+        match (kinds.next(), kinds.next()) {
+            (None, _) => MoveKind::Swap,
+            // Don't use up random numbers when there's no choice to make.
+            (Some(kind), None) => *kind,
+            _ => {
+                let weights = MoveKind::ALL.iter().map(|kind| self.weight(*kind));
+                let distribution = WeightedIndex::new(weights).expect("Some weight is positive.");
+                MoveKind::ALL[distribution.sample(&mut rng)]
+            }
+        }
+    }
+}
+
+impl Default for MoveMix {
+    fn default() -> Self {
+        Self::only(MoveKind::Swap)
+    }
+}
+
+/// Parses weights like `swap=2,cycle=1,chain=1`. Kinds that aren't
+/// mentioned are never tried, and a kind without a weight has weight 1.
+impl FromStr for MoveMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let zero = Float::new(0.0).unwrap();
+        let mut mix = Self { weights: [zero; 3] };
+        for part in s.split(',') {
+            let (kind, weight): (&str, f64) = match part.split_once('=') {
+                Some((kind, weight)) => (kind, weight.trim().parse()?),
+                None => (part, 1.0),
+            };
+            if !(weight.is_finite() && weight >= 0.0) {
+                return Err(anyhow!("Move weights can't be negative"));
+            }
+            mix = mix.with_weight(kind.trim().parse()?, weight);
+        }
+        if mix.weights.iter().all(|weight| *weight == zero) {
+            return Err(anyhow!("At least one kind of move needs a positive weight"));
+        }
+        Ok(mix)
+    }
+}
+
+/// How the guest who starts a swap, relocation or ejection chain is picked.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub enum GuestSampling {
+    /// Picks a random table, then a random guest at it.
+    #[default]
+    Uniform,
+    /// Picks guests with a chance that goes up the less happy they are
+    /// than the happiest guest.
+    Unhappiest,
+    /// Picks guests with a chance that goes up the more they'd gain by
+    /// moving to the table they like most, ignoring where they'd sit.
+    MostToGain,
+}
+
+impl FromStr for GuestSampling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "uniform" | "random" => Ok(GuestSampling::Uniform),
+            "unhappiest" | "unhappy" => Ok(GuestSampling::Unhappiest),
+            "most-to-gain" | "gain" => Ok(GuestSampling::MostToGain),
+            _ => Err(anyhow!("Unrecognised guest sampling")),
+        }
+    }
+}
+
+// The chance of picking each seat. Everyone's chance can change when a
+// move is kept, but only the tables the move touched need looking at
+// again to work them out.
+pub(crate) struct SeatSampler<'a> {
+    sampling: GuestSampling,
+    evaluator: IncrementalEvaluator<'a>,
+    // For `MostToGain`, index by guest and then table to get how the
+    // guest feels about everyone sitting there.
+    affinities: Vec<Vec<i64>>,
+    seats: Vec<(usize, usize)>,
+    // `None` if nobody can move.
+    distribution: Option<WeightedIndex<f64>>,
+}
+
+impl<'a> SeatSampler<'a> {
+    pub(crate) fn new(
+        sampling: GuestSampling,
+        neighbourhood: &Neighbourhood<'a>,
+        plan: &[Vec<usize>],
+    ) -> Self {
+        let problem = neighbourhood.problem;
+        let affinities = match sampling {
+            GuestSampling::MostToGain => (0..problem.relations().len())
+                .map(|guest| {
+                    plan.iter()
+                        .map(|table| affinity(problem, guest, table))
+                        .collect()
+                })
+                .collect(),
+            GuestSampling::Uniform | GuestSampling::Unhappiest => Vec::new(),
+        };
+
+        let mut sampler = Self {
+            sampling,
+            evaluator: IncrementalEvaluator::new(problem, plan),
+            affinities,
+            seats: Vec::new(),
+            distribution: None,
+        };
+        sampler.reweigh(neighbourhood, plan);
+        sampler
+    }
+
+    // Catches up with a move kept in the plan, which only changed who
+    // sits at the given tables.
+    pub(crate) fn update(
+        &mut self,
+        neighbourhood: &Neighbourhood,
+        plan: &[Vec<usize>],
+        tables: &[usize],
+    ) {
+        self.evaluator.refresh_tables(plan, tables);
+        for (guest, affinities) in self.affinities.iter_mut().enumerate() {
+            for &table in tables {
+                affinities[table] = affinity(neighbourhood.problem, guest, &plan[table]);
+            }
+        }
+        self.reweigh(neighbourhood, plan);
+    }
+
+    pub(crate) fn sample<R: Rng>(&self, mut rng: R) -> Option<(usize, usize)> {
+        let distribution = self.distribution.as_ref()?;
+        Some(self.seats[distribution.sample(&mut rng)])
+    }
+
+    fn reweigh(&mut self, neighbourhood: &Neighbourhood, plan: &[Vec<usize>]) {
+        let problem = neighbourhood.problem;
+        let happinesses = self.evaluator.happinesses();
+
+        let badness: Vec<i64> = match self.sampling {
+            GuestSampling::Uniform => vec![0; happinesses.len()],
+            GuestSampling::Unhappiest => {
+                let happiest = happinesses.iter().copied().max().unwrap_or(0);
+                happinesses.iter().map(|h| happiest - h).collect()
+            }
+            GuestSampling::MostToGain => self
+                .affinities
+                .iter()
+                .enumerate()
+                .map(|(guest, affinities)| {
+                    let table = self.evaluator.table_of(guest);
+                    let best_elsewhere = affinities
+                        .iter()
+                        .enumerate()
+                        .filter(|(t, _)| *t != table && problem.allows(guest, *t))
+                        .map(|(_, affinity)| *affinity)
+                        .max();
+                    best_elsewhere.map_or(0, |best| (best - happinesses[guest]).max(0))
+                })
+                .collect(),
+        };
+
+        self.seats = plan
+            .iter()
+            .enumerate()
+            .flat_map(|(table, guests)| (0..guests.len()).map(move |seat| (table, seat)))
+            .collect();
+        // Everyone who can move has some chance of being picked.
+        let weights = self.seats.iter().map(|(table, seat)| {
+            let guest = plan[*table][*seat];
+            if neighbourhood.is_pinned(guest) {
+                0.0
+            } else {
+                (badness[guest] + 1) as f64
+            }
+        });
+        self.distribution = WeightedIndex::new(weights).ok();
+    }
+}
+
+// How much the guest likes everyone at the table put together.
+fn affinity(problem: &Problem, guest: usize, table: &[usize]) -> i64 {
+    table
+        .iter()
+        .map(|other| problem.relations().relationship(guest, *other))
+        .sum()
+}
+
+// Proposes random moves of one kind. The move may still turn out to
+// move a pinned guest, or a guest to a table they're forbidden from,
+// which the neighbourhood checks afterwards. See `MoveKind` for why
+// this is private.
+pub(crate) trait MoveGenerator {
+    // Returns `None` if this kind of move can't be made from where
+    // the generator happened to start.
+    fn propose(
+        &self,
+        neighbourhood: &Neighbourhood,
+        rng: &mut dyn RngCore,
+        plan: &[Vec<usize>],
+    ) -> Option<Move>;
+}
+
+pub(crate) struct InterTableSwaps;
+
+impl MoveGenerator for InterTableSwaps {
+    fn propose(
+        &self,
+        neighbourhood: &Neighbourhood,
+        rng: &mut dyn RngCore,
+        plan: &[Vec<usize>],
+    ) -> Option<Move> {
+        neighbourhood.swap_or_relocation(rng, plan)
+    }
+}
+
+pub(crate) struct ThreeCycles;
+
+impl MoveGenerator for ThreeCycles {
+    fn propose(
+        &self,
+        neighbourhood: &Neighbourhood,
+        rng: &mut dyn RngCore,
+        plan: &[Vec<usize>],
+    ) -> Option<Move> {
+        let tables = occupied_tables(plan);
+        if tables.len() < 3 {
+            return None;
+        }
+        let tables: Vec<usize> = tables.choose_multiple(rng, 3).copied().collect();
+        let seats: Vec<(usize, usize)> = tables
+            .into_iter()
+            .map(|table| (table, rng.gen_range(0..plan[table].len())))
+            .collect();
+
+        // Groups would need someone to come back the other way.
+        let only_singles = seats
+            .iter()
+            .all(|(table, seat)| neighbourhood.group(plan[*table][*seat]).len() == 1);
+        if only_singles {
+            Some(Move::Chain(Chain {
+                seats,
+                end_table: None,
+            }))
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) struct EjectionChains;
+
+impl MoveGenerator for EjectionChains {
+    fn propose(
+        &self,
+        neighbourhood: &Neighbourhood,
+        rng: &mut dyn RngCore,
+        plan: &[Vec<usize>],
+    ) -> Option<Move> {
+        let problem = neighbourhood.problem;
+        let relations = problem.relations();
+        let is_loose =
+            |guest: usize| neighbourhood.group(guest).len() == 1 && !neighbourhood.is_pinned(guest);
+        // How much the guest and the people at the table like each other.
+        let affinity = |guest: usize, table: usize| -> i64 {
+            plan[table]
+                .iter()
+                .filter(|other| **other != guest)
+                .map(|other| {
+                    relations.relationship(guest, *other) + relations.relationship(*other, guest)
+                })
+                .sum()
+        };
+
+        let (first_table, first_seat) = match neighbourhood.targeted_seat(&mut *rng, plan) {
+            Some(seat) => seat,
+            None => {
+                let table = *occupied_tables(plan).choose(rng)?;
+                (table, rng.gen_range(0..plan[table].len()))
+            }
+        };
+        if !is_loose(plan[first_table][first_seat]) {
+            return None;
+        }
+        let can_leave_seat_empty = plan[first_table].len() > problem.min_occupancy;
+
+        let mut seats = vec![(first_table, first_seat)];
+        let mut visited = vec![false; plan.len()];
+        visited[first_table] = true;
+        while seats.len() < MAX_CHAIN_LENGTH {
+            let (table, seat) = *seats.last().expect("Chains are never empty.");
+            let guest = plan[table][seat];
+
+            // Shuffle first, so ties are broken at random.
+            let mut tables: Vec<usize> = (0..plan.len())
+                .filter(|t| !visited[*t] && neighbourhood.can_move(guest, *t))
+                .collect();
+            tables.shuffle(rng);
+            let next_table = match tables.into_iter().max_by_key(|t| affinity(guest, *t)) {
+                Some(next_table) => next_table,
+                None => break,
+            };
+
+            if can_leave_seat_empty && plan[next_table].len() < problem.capacity(next_table) {
+                return Some(Move::Chain(Chain {
+                    seats,
+                    end_table: Some(next_table),
+                }));
+            }
+
+            let next_seat = (0..plan[next_table].len())
+                .filter(|s| is_loose(plan[next_table][*s]))
+                .min_by_key(|s| affinity(plan[next_table][*s], next_table));
+            match next_seat {
+                Some(next_seat) => {
+                    visited[next_table] = true;
+                    seats.push((next_table, next_seat));
+                }
+                None => break,
+            }
+        }
+
+        // Close the chain, so the last guest takes the first seat.
+        if seats.len() > 1 {
+            Some(Move::Chain(Chain {
+                seats,
+                end_table: None,
+            }))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hill_climb::{make_move, random_plan, undo_move};
+    use crate::metrics::Metrics;
+    use crate::test_util::{
+        assert_feasible, assert_improves_on_start, assert_partition, constrained_wedding,
+        random_wedding,
+    };
+    use crate::{GuestRelations, HillClimbingPlanner, LahcPlanner, Plan, Problem, SeatingPlanner};
+
+    #[test]
+    fn parse_move_mix() {
+        let mix: MoveMix = "swap=2, cycle, chain=0.5".parse().unwrap();
+        assert_eq!(mix.weight(MoveKind::Swap), 2.0);
+        assert_eq!(mix.weight(MoveKind::Cycle), 1.0);
+        assert_eq!(mix.weight(MoveKind::EjectionChain), 0.5);
+
+        assert_eq!(
+            "chain".parse::<MoveMix>().unwrap(),
+            MoveMix::only(MoveKind::EjectionChain)
+        );
+        assert!("swap=-1".parse::<MoveMix>().is_err());
+        assert!("swap=0".parse::<MoveMix>().is_err());
+        assert!("shuffle".parse::<MoveMix>().is_err());
+    }
+
+    #[test]
+    fn moves_are_chosen_by_weight() {
+        let mix: MoveMix = "swap=3, cycle=1".parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            counts[mix.choose(&mut rng) as usize] += 1;
+        }
+        assert_eq!(counts[MoveKind::EjectionChain as usize], 0);
+        assert!((2800..3200).contains(&counts[MoveKind::Swap as usize]));
+    }
+
+    #[test]
+    fn every_move_changes_something() {
+        let problem = random_wedding(&mut StdRng::seed_from_u64(0), 45, 6)
+            .with_min_occupancy(6)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1], vec![2, 3, 4]], vec![])
+            .unwrap()
+            .with_pins(vec![(5, 0), (6, 1)], vec![(7, 2), (7, 3)])
+            .unwrap();
+
+        for kind in MoveKind::ALL {
+            let neighbourhood = Neighbourhood::new(&problem).with_moves(MoveMix::only(kind));
+            let mut rng = StdRng::seed_from_u64(1);
+            let mut plan = random_plan(&mut rng, &problem).unwrap();
+            for _ in 0..1000 {
+                let change = neighbourhood.random_move(&mut rng, &plan).unwrap();
+                let before = plan.clone();
+                make_move(&mut plan, &change);
+                assert_ne!(plan, before, "{:?} proposed {:?}", kind, change);
+
+                let mut undone = plan.clone();
+                undo_move(&mut undone, &change);
+                assert_eq!(undone, before);
+
+                assert!(plan.iter().all(|table| (6..=8).contains(&table.len())));
+                assert_eq!(Metrics::new(&plan, &problem).together_violations(), 0);
+                assert!(plan[0].contains(&5) && plan[1].contains(&6));
+                assert!(!plan[2].contains(&7) && !plan[3].contains(&7));
+            }
+            assert_partition(&plan, 45);
+        }
+    }
+
+    #[test]
+    fn lahc_with_mixed_moves_never_worse_than_start() {
+        let mix = MoveMix::only(MoveKind::Swap)
+            .with_weight(MoveKind::Cycle, 1.0)
+            .with_weight(MoveKind::EjectionChain, 1.0);
+        for seed in 0..3 {
+            assert_improves_on_start(seed, 30, 5, |rng| {
+                LahcPlanner::new(rng, Float::new(0.01).unwrap()).with_moves(mix)
+            });
+        }
+    }
+
+    // Guest 0 is at a table of guests who dislike them, next to a table of
+    // their friends. Guest 1 is pinned.
+    fn misplaced_guest() -> (Problem, Plan) {
+        let matrix = (0..8)
+            .map(|i: usize| {
+                (0..8)
+                    .map(|j: usize| match (i.min(j), i.max(j)) {
+                        (0, 1..=3) => -10,
+                        (0, 4..=7) => 10,
+                        _ => 0,
+                    })
+                    .collect()
+            })
+            .collect();
+        let problem = Problem::with_capacities(GuestRelations::new(matrix).unwrap(), vec![4, 5])
+            .unwrap()
+            .with_pins(vec![(1, 0)], vec![])
+            .unwrap();
+        (problem, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]])
+    }
+
+    #[test]
+    fn targeted_sampling_favours_misplaced_guests() {
+        let (problem, plan) = misplaced_guest();
+        for sampling in [GuestSampling::Unhappiest, GuestSampling::MostToGain] {
+            let neighbourhood = Neighbourhood::new(&problem).with_sampling(sampling);
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut counts = [0; 8];
+            for _ in 0..1000 {
+                let (table, seat) = neighbourhood.targeted_seat(&mut rng, &plan).unwrap();
+                counts[plan[table][seat]] += 1;
+            }
+            assert_eq!(counts[1], 0, "{:?} picked a pinned guest", sampling);
+            assert!(
+                counts[0] > 500,
+                "{:?} only picked guest 0 {} times",
+                sampling,
+                counts[0]
+            );
+        }
+
+        let neighbourhood = Neighbourhood::new(&problem);
+        assert_eq!(
+            neighbourhood.targeted_seat(StdRng::seed_from_u64(0), &plan),
+            None
+        );
+    }
+
+    #[test]
+    fn targeted_sampling_follows_the_plan() {
+        let (problem, mut plan) = misplaced_guest();
+        let neighbourhood = Neighbourhood::new(&problem).with_sampling(GuestSampling::MostToGain);
+        let mut rng = StdRng::seed_from_u64(0);
+        neighbourhood.targeted_seat(&mut rng, &plan).unwrap();
+
+        // Once guest 0 is with their friends, they have nothing to gain.
+        plan = vec![vec![1, 2, 3], vec![4, 5, 6, 7, 0]];
+        neighbourhood.forget_plan();
+        let picked_0 = (0..1000)
+            .map(|_| neighbourhood.targeted_seat(&mut rng, &plan).unwrap())
+            .filter(|(table, seat)| plan[*table][*seat] == 0)
+            .count();
+        assert!(picked_0 < 500);
+    }
+
+    #[test]
+    fn targeted_sampling_keeps_up_with_kept_moves() {
+        let problem = random_wedding(&mut StdRng::seed_from_u64(0), 30, 4)
+            .with_pins(vec![(0, 0)], vec![(1, 1)])
+            .unwrap();
+        for sampling in [GuestSampling::Unhappiest, GuestSampling::MostToGain] {
+            let neighbourhood = Neighbourhood::new(&problem).with_sampling(sampling);
+            let mut rng = StdRng::seed_from_u64(1);
+            let mut plan = random_plan(&mut rng, &problem).unwrap();
+            for _ in 0..100 {
+                let change = neighbourhood.random_move(&mut rng, &plan).unwrap();
+                make_move(&mut plan, &change);
+                neighbourhood.kept(&plan, &change);
+
+                // Seats are picked just as if the plan were new.
+                let fresh = Neighbourhood::new(&problem).with_sampling(sampling);
+                let seed = rng.gen();
+                assert_eq!(
+                    neighbourhood.targeted_seat(StdRng::seed_from_u64(seed), &plan),
+                    fresh.targeted_seat(StdRng::seed_from_u64(seed), &plan),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hill_climbing_with_each_sampling() {
+        let samplings = [
+            GuestSampling::Uniform,
+            GuestSampling::Unhappiest,
+            GuestSampling::MostToGain,
+        ];
+        for (seed, &sampling) in samplings.iter().enumerate() {
+            let seed = seed as u64;
+            let planner = |rng| {
+                HillClimbingPlanner::new(rng, Float::new(0.01).unwrap()).with_sampling(sampling)
+            };
+            assert_improves_on_start(seed, 30, 5, planner);
+
+            let problem = constrained_wedding(seed);
+            let plan = planner(StdRng::seed_from_u64(seed)).plan(&problem).unwrap();
+            assert_feasible(&plan, &problem);
+        }
+    }
+}
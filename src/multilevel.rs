@@ -0,0 +1,535 @@
+use crate::hill_climb::{check_feasible, check_groups, random_plan, Neighbourhood};
+use crate::objective::{Objective, TotalHappiness};
+use crate::{
+    ClimbMode, GuestRelations, HillClimbingPlanner, LocalSearch, Plan, PlanError, Problem,
+    SeatingPlanner,
+};
+
+use rand::prelude::*;
+
+type Float = ordered_float::NotNan<f64>;
+
+// We stop coarsening once there are this few clusters per table.
+const COARSEST_CLUSTERS_PER_TABLE: usize = 4;
+
+// We also stop once a level merges fewer than this fraction of the clusters,
+// as the rest are unlikely to merge either.
+const MIN_SHRINKAGE: f64 = 0.1;
+
+// The most times we go through every cluster at a level, in case it keeps
+// finding improvements too small to matter.
+const MAX_SWEEPS: usize = 100;
+
+/// Solves big problems by first merging guests who like each other most
+/// into clusters, level by level, until there are only a few clusters per
+/// table. Each level is a smaller problem, where the guests are clusters
+/// who like each other as much as their members do in total. The coarsest
+/// clusters are seated and moved around until no move helps. Then each
+/// level is undone in turn, splitting the clusters back up and moving the
+/// smaller clusters around. Finally, hill climbing improves the plan for
+/// the guests themselves.
+///
+/// Only the last step uses the objective and climbing mode: the levels
+/// before it maximise total happiness, without any seating layout.
+///
+/// No cluster is bigger than half the smallest table, so they pack around
+/// the tables easily, and if the coarsest clusters still don't fit, the
+/// plan starts from a finer level. Guests who must be kept apart are never
+/// merged, nor are guests who couldn't sit at the same table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultilevelPlanner<R, O = TotalHappiness> {
+    rng: R,
+    termination_threshold: Float,
+    objective: O,
+    mode: ClimbMode,
+}
+
+impl<R> MultilevelPlanner<R>
+where
+    R: Rng,
+{
+    pub fn new(rng: R, termination_threshold: Float) -> Self {
+        Self::with_objective(rng, termination_threshold, TotalHappiness)
+    }
+}
+
+impl<R, O> MultilevelPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    pub fn with_objective(rng: R, termination_threshold: Float, objective: O) -> Self {
+        Self {
+            rng,
+            termination_threshold,
+            objective,
+            mode: ClimbMode::default(),
+        }
+    }
+
+    /// Chooses how the hill climbing at each level finds improving moves.
+    pub fn with_mode(self, mode: ClimbMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    // Improves the plan for the guests themselves.
+    fn refine(&mut self, problem: &Problem, plan: &mut Plan) {
+        let neighbourhood = Neighbourhood::new(problem);
+        HillClimbingPlanner::with_objective(
+            &mut self.rng,
+            self.termination_threshold,
+            &self.objective,
+        )
+        .with_mode(self.mode)
+        .climb(&neighbourhood, plan);
+    }
+}
+
+impl<R, O> SeatingPlanner for MultilevelPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let levels = coarsen(&mut self.rng, problem);
+
+        // Seat the coarsest clusters we can pack around the tables.
+        let (coarsest, mut plan) = levels
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, clusters)| {
+                let level = with_clusters(problem, clusters);
+                random_plan(&mut self.rng, &level)
+                    .ok()
+                    .map(|plan| (i, plan))
+            })
+            .ok_or(PlanError::Unpackable)?;
+
+        for clusters in levels[1..=coarsest].iter().rev() {
+            Level::new(problem, clusters).climb(&mut self.rng, &mut plan);
+        }
+        self.refine(problem, &mut plan);
+
+        check_feasible(plan, problem)
+    }
+}
+
+impl<R, O> LocalSearch for MultilevelPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    // Only refines the plan at the finest level, as a plan that doesn't
+    // keep the clusters together can't be coarsened.
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        self.refine(problem, &mut plan);
+        check_feasible(plan, problem)
+    }
+}
+
+// The problem with the clusters added as groups who must sit together,
+// which `random_plan` knows how to pack around the tables.
+fn with_clusters(problem: &Problem, clusters: &[Vec<usize>]) -> Problem {
+    let together = clusters
+        .iter()
+        .filter(|cluster| cluster.len() > 1)
+        .cloned()
+        .collect();
+    problem
+        .clone()
+        .with_constraints(together, problem.apart().to_vec())
+        .expect("Clusters only merge guests who can sit together.")
+}
+
+// One level of coarsening, as a problem of its own.
+struct Level<'a> {
+    original: &'a Problem,
+    // Index by cluster to get the guests in it.
+    clusters: &'a [Vec<usize>],
+    // Each cluster is a guest here, who must sit wherever all its members
+    // may, and apart from any cluster one of them must be kept apart from.
+    // Nobody has a minimum occupancy, as the clusters are different sizes.
+    problem: Problem,
+    apart_from: Vec<Vec<usize>>,
+}
+
+impl<'a> Level<'a> {
+    fn new(original: &'a Problem, clusters: &'a [Vec<usize>]) -> Self {
+        let n_clusters = clusters.len();
+        let mut cluster_of = vec![0; original.relations().len()];
+        for (id, cluster) in clusters.iter().enumerate() {
+            for guest in cluster {
+                cluster_of[*guest] = id;
+            }
+        }
+
+        let mut bonds = bonds(original, clusters, &cluster_of);
+        for (cluster, row) in bonds.iter_mut().enumerate() {
+            row[cluster] = 0;
+        }
+        let mut apart: Vec<(usize, usize)> = original
+            .apart()
+            .iter()
+            .map(|(i, j)| {
+                let (i, j) = (cluster_of[*i], cluster_of[*j]);
+                (i.min(j), i.max(j))
+            })
+            .collect();
+        apart.sort_unstable();
+        apart.dedup();
+        let pins = (0..n_clusters)
+            .filter_map(|cluster| {
+                let mut pins = clusters[cluster]
+                    .iter()
+                    .filter_map(|guest| original.pinned_table(*guest));
+                pins.next().map(|table| (cluster, table))
+            })
+            .collect();
+        let forbidden = original
+            .forbidden()
+            .iter()
+            .map(|(guest, table)| (cluster_of[*guest], *table))
+            .collect();
+
+        let problem = GuestRelations::new(bonds)
+            .map_err(Into::into)
+            .and_then(|relations| {
+                Problem::with_capacities(relations, original.capacities().collect())
+            })
+            .and_then(|problem| problem.with_constraints(vec![], apart))
+            .and_then(|problem| problem.with_pins(pins, forbidden))
+            .expect("Clusters only merge guests who can sit together.");
+
+        let mut apart_from = vec![Vec::new(); n_clusters];
+        for &(i, j) in problem.apart() {
+            apart_from[i].push(j);
+            apart_from[j].push(i);
+        }
+
+        Self {
+            original,
+            clusters,
+            problem,
+            apart_from,
+        }
+    }
+
+    fn size(&self, cluster: usize) -> usize {
+        self.clusters[cluster].len()
+    }
+
+    // Moves each cluster in turn to another table, or swaps it with a
+    // cluster there, whichever adds the most happiness, until nothing
+    // does. The clusters are kept together in the plan.
+    fn climb<R: Rng>(&self, mut rng: R, plan: &mut Plan) {
+        let n_clusters = self.clusters.len();
+        let relations = self.problem.relations();
+
+        let mut seated_at = vec![0; self.original.relations().len()];
+        for (table, guests) in plan.iter().enumerate() {
+            for guest in guests {
+                seated_at[*guest] = table;
+            }
+        }
+        let mut table_of: Vec<usize> = self
+            .clusters
+            .iter()
+            .map(|cluster| seated_at[cluster[0]])
+            .collect();
+        let mut occupancy: Vec<usize> = plan.iter().map(Vec::len).collect();
+        // How much each cluster likes the clusters at each table.
+        let mut liking = vec![vec![0; plan.len()]; n_clusters];
+        for (cluster, row) in liking.iter_mut().enumerate() {
+            for (other, table) in table_of.iter().enumerate() {
+                row[*table] += relations.relationship(cluster, other);
+            }
+        }
+
+        let fits = |table: usize, n_guests: usize| {
+            n_guests >= self.original.min_occupancy && n_guests <= self.problem.capacity(table)
+        };
+        let can_join = |table_of: &[usize], cluster: usize, table: usize, leaving: usize| {
+            self.problem.allows(cluster, table)
+                && self.apart_from[cluster]
+                    .iter()
+                    .all(|other| table_of[*other] != table || *other == leaving)
+        };
+
+        let mut order: Vec<usize> = (0..n_clusters)
+            .filter(|cluster| self.problem.pinned_table(*cluster).is_none())
+            .collect();
+        for _ in 0..MAX_SWEEPS {
+            order.shuffle(&mut rng);
+            let mut improved = false;
+            for &cluster in &order {
+                let (from, size) = (table_of[cluster], self.size(cluster));
+                let relocations = (0..plan.len())
+                    .filter(|to| {
+                        *to != from
+                            && fits(from, occupancy[from] - size)
+                            && fits(*to, occupancy[*to] + size)
+                            && can_join(&table_of, cluster, *to, cluster)
+                    })
+                    .map(|to| {
+                        let gain = liking[cluster][to] - liking[cluster][from];
+                        (gain, to, None)
+                    });
+                let swaps = order
+                    .iter()
+                    .filter(|other| {
+                        let (to, other_size) = (table_of[**other], self.size(**other));
+                        to != from
+                            && fits(from, occupancy[from] + other_size - size)
+                            && fits(to, occupancy[to] + size - other_size)
+                            && can_join(&table_of, cluster, to, **other)
+                            && can_join(&table_of, **other, from, cluster)
+                    })
+                    .map(|other| {
+                        let to = table_of[*other];
+                        let gain = liking[cluster][to] - liking[cluster][from]
+                            + liking[*other][from]
+                            - liking[*other][to]
+                            - 2 * relations.relationship(cluster, *other);
+                        (gain, to, Some(*other))
+                    });
+                let best = relocations.chain(swaps).max_by_key(|(gain, ..)| *gain);
+
+                if let Some((gain, to, other)) = best {
+                    if gain <= 0 {
+                        continue;
+                    }
+                    improved = true;
+                    let mut moves = vec![(cluster, from, to)];
+                    moves.extend(other.map(|other| (other, to, from)));
+                    for (moved, from, to) in moves {
+                        table_of[moved] = to;
+                        occupancy[from] -= self.size(moved);
+                        occupancy[to] += self.size(moved);
+                        for (other, row) in liking.iter_mut().enumerate() {
+                            let relationship = relations.relationship(other, moved);
+                            row[from] -= relationship;
+                            row[to] += relationship;
+                        }
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        for guests in plan.iter_mut() {
+            guests.clear();
+        }
+        for (cluster, table) in table_of.into_iter().enumerate() {
+            plan[table].extend_from_slice(&self.clusters[cluster]);
+        }
+    }
+}
+
+// How much each pair of clusters like each other, and each cluster itself.
+fn bonds(problem: &Problem, clusters: &[Vec<usize>], cluster_of: &[usize]) -> Vec<Vec<i64>> {
+    let mut bonds = vec![vec![0; clusters.len()]; clusters.len()];
+    for (guest, row) in problem.relations().iter().enumerate() {
+        for (other, relationship) in row.enumerate() {
+            bonds[cluster_of[guest]][cluster_of[other]] += relationship;
+        }
+    }
+    bonds
+}
+
+// Returns the clusters at each level, starting with the groups who must
+// sit together.
+fn coarsen<R: Rng>(mut rng: R, problem: &Problem) -> Vec<Vec<Vec<usize>>> {
+    let relations = problem.relations();
+    let n_guests = relations.len();
+    let max_cluster_size = (problem.capacities().min().unwrap_or(1) / 2).max(1);
+
+    let mut apart_from = vec![Vec::new(); n_guests];
+    for &(i, j) in problem.apart() {
+        apart_from[i].push(j);
+        apart_from[j].push(i);
+    }
+    let can_merge = |cluster1: &[usize], cluster2: &[usize]| {
+        let mut pins: Vec<usize> = cluster1
+            .iter()
+            .chain(cluster2)
+            .filter_map(|guest| problem.pinned_table(*guest))
+            .collect();
+        pins.sort_unstable();
+        pins.dedup();
+        let can_sit_together = (0..problem.n_tables).any(|table| {
+            cluster1
+                .iter()
+                .chain(cluster2)
+                .all(|guest| problem.allows(*guest, table))
+        });
+        cluster1.len() + cluster2.len() <= max_cluster_size
+            && pins.len() <= 1
+            && can_sit_together
+            && !cluster1.iter().any(|guest| {
+                apart_from[*guest]
+                    .iter()
+                    .any(|other| cluster2.contains(other))
+            })
+    };
+
+    let mut levels = vec![problem.units()];
+    loop {
+        let clusters = levels.last().expect("There is always a level.");
+        if clusters.len() <= COARSEST_CLUSTERS_PER_TABLE * problem.n_tables {
+            break;
+        }
+
+        let mut cluster_of = vec![0; n_guests];
+        for (id, cluster) in clusters.iter().enumerate() {
+            for guest in cluster {
+                cluster_of[*guest] = id;
+            }
+        }
+        let bonds = bonds(problem, clusters, &cluster_of);
+
+        // Merge each cluster with the one it likes most, if it's free.
+        let mut order: Vec<usize> = (0..clusters.len()).collect();
+        order.shuffle(&mut rng);
+        let mut merged = vec![false; clusters.len()];
+        let mut next = Vec::with_capacity(clusters.len());
+        for cluster in order {
+            if merged[cluster] {
+                continue;
+            }
+            merged[cluster] = true;
+            let partner = (0..clusters.len())
+                .filter(|other| !merged[*other] && bonds[cluster][*other] > 0)
+                .filter(|other| can_merge(&clusters[cluster], &clusters[*other]))
+                .max_by_key(|other| bonds[cluster][*other]);
+
+            let mut new_cluster = clusters[cluster].clone();
+            if let Some(partner) = partner {
+                merged[partner] = true;
+                new_cluster.extend_from_slice(&clusters[partner]);
+            }
+            next.push(new_cluster);
+        }
+
+        let shrinkage = 1.0 - next.len() as f64 / clusters.len() as f64;
+        if shrinkage < MIN_SHRINKAGE {
+            break;
+        }
+        levels.push(next);
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::metrics::Metrics;
+    use crate::test_util::{assert_feasible, assert_partition};
+    use crate::{GuestRelations, Symmetrize};
+
+    // Cliques of four, who like each other and nobody else, and a little
+    // noise.
+    fn clustered_wedding(rng: &mut impl Rng, n_guests: usize, n_tables: usize) -> Problem {
+        let matrix = (0..n_guests)
+            .map(|i| {
+                (0..n_guests)
+                    .map(|j| match (i == j, i / 4 == j / 4) {
+                        (true, _) => 0,
+                        (false, true) => 10,
+                        (false, false) => rng.gen_range(-1..=1),
+                    })
+                    .collect()
+            })
+            .collect();
+        let relations = GuestRelations::symmetrized(matrix, Symmetrize::Max).unwrap();
+        Problem::new(relations, n_tables).unwrap()
+    }
+
+    #[test]
+    fn clusters_fit_at_tables() {
+        let problem = clustered_wedding(&mut StdRng::seed_from_u64(0), 200, 20)
+            .with_constraints(vec![], vec![(0, 1)])
+            .unwrap();
+        let levels = coarsen(StdRng::seed_from_u64(0), &problem);
+        assert!(levels.len() > 2);
+        for clusters in &levels {
+            assert_partition(clusters, 200);
+            assert!(clusters.iter().all(|cluster| cluster.len() <= 5));
+            assert!(!clusters
+                .iter()
+                .any(|cluster| cluster.contains(&0) && cluster.contains(&1)));
+        }
+    }
+
+    #[test]
+    fn levels_are_smaller_problems() {
+        let problem = clustered_wedding(&mut StdRng::seed_from_u64(3), 100, 10)
+            .with_constraints(vec![], vec![(0, 4)])
+            .unwrap();
+        let levels = coarsen(StdRng::seed_from_u64(3), &problem);
+        let clusters = &levels[1];
+        let level = Level::new(&problem, clusters);
+        assert_eq!(level.problem.relations().len(), clusters.len());
+        assert_eq!(level.problem.apart().len(), 1);
+
+        let (cluster1, cluster2) = (&clusters[0], &clusters[1]);
+        let bond: i64 = cluster1
+            .iter()
+            .flat_map(|guest| cluster2.iter().map(move |other| (*guest, *other)))
+            .map(|(guest, other)| problem.relations().relationship(guest, other))
+            .sum();
+        assert_eq!(level.problem.relations().relationship(0, 1), bond);
+
+        // Climbing keeps each cluster together, and never makes things worse.
+        let start =
+            random_plan(StdRng::seed_from_u64(3), &with_clusters(&problem, clusters)).unwrap();
+        let mut plan = start.clone();
+        level.climb(StdRng::seed_from_u64(3), &mut plan);
+        assert_feasible(&plan, &problem);
+        for cluster in clusters {
+            assert!(plan
+                .iter()
+                .any(|guests| cluster.iter().all(|guest| guests.contains(guest))));
+        }
+        assert!(
+            Metrics::new(&plan, &problem).total_happiness()
+                >= Metrics::new(&start, &problem).total_happiness()
+        );
+    }
+
+    #[test]
+    fn multilevel_beats_hill_climbing_from_random() {
+        let problem = clustered_wedding(&mut StdRng::seed_from_u64(1), 200, 20);
+        let threshold = Float::new(0.01).unwrap();
+
+        let multilevel = MultilevelPlanner::new(StdRng::seed_from_u64(1), threshold)
+            .plan(&problem)
+            .unwrap();
+        let climbed = HillClimbingPlanner::new(StdRng::seed_from_u64(1), threshold)
+            .plan(&problem)
+            .unwrap();
+        assert!(
+            Metrics::new(&multilevel, &problem).total_happiness()
+                > Metrics::new(&climbed, &problem).total_happiness()
+        );
+    }
+
+    #[test]
+    fn multilevel_respects_constraints() {
+        let problem = clustered_wedding(&mut StdRng::seed_from_u64(2), 60, 6)
+            .with_min_occupancy(9)
+            .unwrap()
+            .with_constraints(vec![vec![0, 5, 9]], vec![(1, 2), (2, 3)])
+            .unwrap()
+            .with_pins(vec![(4, 5), (6, 5)], vec![(7, 5)])
+            .unwrap();
+        let plan = MultilevelPlanner::new(StdRng::seed_from_u64(2), Float::new(0.01).unwrap())
+            .plan(&problem)
+            .unwrap();
+        assert_feasible(&plan, &problem);
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::metrics::Metrics;
+use crate::{Plan, Problem};
+
+type Float = ordered_float::NotNan<f64>;
+
+/// Something a planner can try to maximise.
+pub trait Objective {
+    /// Higher scores are better.
+    type Score: Ord + Copy + Debug;
+
+    /// Scores a plan from its metrics.
+    fn evaluate(&self, metrics: &Metrics) -> Self::Score;
+
+    /// Scores a plan for a problem.
+    fn score(&self, problem: &Problem, plan: &Plan) -> Self::Score {
+        self.evaluate(&Metrics::new(plan, problem))
+    }
+
+    /// Scores a plan from its total happiness alone, if that's all the
+    /// objective depends on. This lets planners score swaps without
+    /// building a whole new `Metrics`.
+    fn evaluate_total(&self, _total_happiness: i64) -> Option<Self::Score> {
+        None
+    }
+}
+
+// Lets planners share an objective without taking ownership of it.
+impl<O> Objective for &O
+where
+    O: Objective + ?Sized,
+{
+    type Score = O::Score;
+
+    fn evaluate(&self, metrics: &Metrics) -> Self::Score {
+        (**self).evaluate(metrics)
+    }
+
+    fn evaluate_total(&self, total_happiness: i64) -> Option<Self::Score> {
+        (**self).evaluate_total(total_happiness)
+    }
+}
+
+/// Scores that lie on a number line, so a planner can tell how much
+/// better one plan is than another, not just which is better.
+pub trait NumericScore: Copy {
+    fn to_f64(self) -> f64;
+}
+
+impl NumericScore for i64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl NumericScore for Float {
+    fn to_f64(self) -> f64 {
+        self.into_inner()
+    }
+}
+
+/// The sum of everyone's happiness.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct TotalHappiness;
+
+impl Objective for TotalHappiness {
+    type Score = i64;
+
+    fn evaluate(&self, metrics: &Metrics) -> i64 {
+        metrics.total_happiness()
+    }
+
+    fn evaluate_total(&self, total_happiness: i64) -> Option<i64> {
+        Some(total_happiness)
+    }
+}
+
+/// The happiness of the worst-off guest.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct Maximin;
+
+impl Objective for Maximin {
+    type Score = i64;
+
+    fn evaluate(&self, metrics: &Metrics) -> i64 {
+        metrics.min_happiness()
+    }
+}
+
+/// As few guests as possible with nobody they like at their table.
+/// Scores are the negated number of lonely guests.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct FewestLonely;
+
+impl Objective for FewestLonely {
+    type Score = i64;
+
+    fn evaluate(&self, metrics: &Metrics) -> i64 {
+        -(metrics.n_lonely() as i64)
+    }
+}
+
+/// First leave as few guests lonely as possible, then maximise
+/// total happiness.
+pub type Fairness = Lexicographic<FewestLonely, TotalHappiness>;
+
+/// Compares plans by the first objective, and only uses the
+/// second to break ties.
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct Lexicographic<A, B>(pub A, pub B);
+
+impl<A, B> Objective for Lexicographic<A, B>
+where
+    A: Objective,
+    B: Objective,
+{
+    type Score = (A::Score, B::Score);
+
+    fn evaluate(&self, metrics: &Metrics) -> Self::Score {
+        (self.0.evaluate(metrics), self.1.evaluate(metrics))
+    }
+}
+
+/// A weighted sum of two objectives.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Weighted<A, B> {
+    first: A,
+    first_weight: f64,
+    second: B,
+    second_weight: f64,
+}
+
+impl<A, B> Weighted<A, B> {
+    /// # Panics
+    /// Panics if either weight is infinite or NaN, as the weighted score
+    /// could then be NaN.
+    pub fn new(first: A, first_weight: f64, second: B, second_weight: f64) -> Self {
+        assert!(first_weight.is_finite() && second_weight.is_finite());
+        Self {
+            first,
+            first_weight,
+            second,
+            second_weight,
+        }
+    }
+}
+
+impl<A, B> Objective for Weighted<A, B>
+where
+    A: Objective<Score = i64>,
+    B: Objective<Score = i64>,
+{
+    type Score = Float;
+
+    fn evaluate(&self, metrics: &Metrics) -> Float {
+        // Huge weights can overflow each term, so they're saturated to
+        // keep the sum from being infinity minus infinity.
+        let first = self.first_weight * self.first.evaluate(metrics) as f64;
+        let second = self.second_weight * self.second.evaluate(metrics) as f64;
+        let sum = first.clamp(f64::MIN, f64::MAX) + second.clamp(f64::MIN, f64::MAX);
+        Float::new(sum).expect("Finite weights never make NaN.")
+    }
+}
+
+/// The objectives that can be chosen when running a solver.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ObjectiveKind {
+    Total,
+    Maximin,
+    Fairness,
+}
+
+impl FromStr for ObjectiveKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "total" => Ok(ObjectiveKind::Total),
+            "maximin" | "min" => Ok(ObjectiveKind::Maximin),
+            "fairness" | "lonely" => Ok(ObjectiveKind::Fairness),
+            _ => Err(anyhow!("Unrecognised objective")),
+        }
+    }
+}
+
+impl ObjectiveKind {
+    /// Calls `f` with the objective this stands for.
+    pub fn apply<F: WithObjective>(self, f: F) -> F::Output {
+        match self {
+            ObjectiveKind::Total => f.with(TotalHappiness),
+            ObjectiveKind::Maximin => f.with(Maximin),
+            ObjectiveKind::Fairness => f.with(Fairness::default()),
+        }
+    }
+}
+
+/// Something to do with an objective that is only chosen at runtime,
+/// such as building a planner for it.
+pub trait WithObjective {
+    type Output;
+
+    fn with<O: Objective>(self, objective: O) -> Self::Output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::GuestRelations;
+
+    #[test]
+    fn objectives_agree_with_metrics() {
+        let relations = GuestRelations::new(vec![
+            vec![0, 5, -1, 0],
+            vec![5, 0, 2, 0],
+            vec![-1, 2, 0, 3],
+            vec![0, 0, 3, 0],
+        ])
+        .unwrap();
+        let problem = Problem::new(relations, 2).unwrap();
+        let plan = vec![vec![0, 1], vec![2, 3]];
+
+        assert_eq!(TotalHappiness.score(&problem, &plan), 16);
+        assert_eq!(Maximin.score(&problem, &plan), 3);
+        assert_eq!(
+            Lexicographic(Maximin, TotalHappiness).score(&problem, &plan),
+            (3, 16)
+        );
+        assert_eq!(
+            Weighted::new(TotalHappiness, 0.5, Maximin, 2.0).score(&problem, &plan),
+            Float::new(14.0).unwrap()
+        );
+
+        // Nobody likes their neighbour.
+        let plan = vec![vec![0, 2], vec![1, 3]];
+        assert_eq!(Fairness::default().score(&problem, &plan), (-4, -2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn weighted_rejects_infinite_weights() {
+        Weighted::new(TotalHappiness, f64::INFINITY, Maximin, 1.0);
+    }
+
+    #[test]
+    fn weighted_survives_overflow() {
+        let relations = GuestRelations::new(vec![vec![0, -1], vec![-1, 0]]).unwrap();
+        let problem = Problem::new(relations, 1).unwrap();
+        let plan = vec![vec![0, 1]];
+
+        // Each term overflows, one to infinity and one to minus infinity.
+        let weighted = Weighted::new(TotalHappiness, f64::MAX, TotalHappiness, -f64::MAX);
+        assert_eq!(weighted.score(&problem, &plan), Float::new(0.0).unwrap());
+    }
+}
@@ -0,0 +1,766 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::Layout;
+use crate::relations::{GuestRelations, RelationsError, UncheckedRelations};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedProblem")]
+pub struct Problem {
+    relations: GuestRelations,
+    pub n_tables: usize,
+    // How many guests each table seats. If this is missing, every table
+    // seats just enough for the guests to fit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacities: Option<Vec<usize>>,
+    // The fewest guests that may be seated at any one table.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub min_occupancy: usize,
+    // Each list of guests must be seated at the same table. These are
+    // typically couples and families, and the planners always move each
+    // list as a single group.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    together: Vec<Vec<usize>>,
+    // Each pair of guests must be seated at different tables.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    apart: Vec<(usize, usize)>,
+    // Guests who must be seated at a particular table, as `(guest, table)`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pins: Vec<(usize, usize)>,
+    // Guests who must not be seated at a particular table, as `(guest, table)`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    forbidden: Vec<(usize, usize)>,
+    // How the seats are arranged. If this is missing, everyone counts
+    // everyone else at their table equally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    // If set, everyone who likes somebody at the wedding must sit at
+    // the same table as at least one person they like.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub require_friend: bool,
+    // `pins` and `forbidden` by guest, built when the problem is validated.
+    #[serde(skip)]
+    seating: Seating,
+    // Whether each guest likes anybody at the wedding, built when the
+    // problem is validated.
+    #[serde(skip)]
+    likes_anyone: Vec<bool>,
+}
+
+// The tables each guest must or mustn't sit at, so they can be looked up
+// without scanning every pin.
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
+struct Seating {
+    pinned: Vec<Option<usize>>,
+    forbidden: Vec<Vec<usize>>,
+}
+
+impl Seating {
+    fn new(problem: &Problem) -> Self {
+        let n_guests = problem.relations.len();
+        let mut pinned = vec![None; n_guests];
+        for &(guest, table) in &problem.pins {
+            pinned[guest].get_or_insert(table);
+        }
+        let mut forbidden = vec![Vec::new(); n_guests];
+        for &(guest, table) in &problem.forbidden {
+            forbidden[guest].push(table);
+        }
+        Self { pinned, forbidden }
+    }
+}
+
+/// The ways in which a problem can be impossible to plan for.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ProblemError {
+    /// The relationship matrix is malformed.
+    Relations(RelationsError),
+    /// There are no tables to seat anyone at.
+    NoTables,
+    /// There isn't exactly one capacity per table.
+    CapacityCount {
+        n_capacities: usize,
+        n_tables: usize,
+    },
+    /// The given table has no seats.
+    NoSeats(usize),
+    /// There aren't enough seats for every guest.
+    TooFewSeats { n_guests: usize, n_seats: usize },
+    /// The given table can't seat the minimum occupancy.
+    UnderCapacity { table: usize, capacity: usize },
+    /// There aren't enough guests to give every table its minimum occupancy.
+    TooFewGuests { n_guests: usize, n_required: usize },
+    /// A constraint mentions a guest who isn't at the wedding.
+    UnknownGuest(usize),
+    /// Some guests who must sit together can't fit at any table.
+    UnitTooLarge { size: usize, max_capacity: usize },
+    /// The two guests must sit both together and apart.
+    Contradiction(usize, usize),
+    /// A constraint mentions a table that doesn't exist.
+    UnknownTable(usize),
+    /// The guest, or someone they must sit with, is pinned to a table
+    /// they can't sit at.
+    ConflictingPins(usize),
+    /// More guests are pinned to the table than it can seat.
+    Overbooked(usize),
+    /// There is neither one table shape for all tables, nor one per table.
+    ShapeCount { n_shapes: usize, n_tables: usize },
+}
+
+impl fmt::Display for ProblemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProblemError::Relations(e) => write!(f, "invalid relationships: {}", e),
+            ProblemError::NoTables => write!(f, "there are no tables"),
+            ProblemError::CapacityCount {
+                n_capacities,
+                n_tables,
+            } => write!(
+                f,
+                "there are {} capacities for {} tables",
+                n_capacities, n_tables
+            ),
+            ProblemError::NoSeats(table) => write!(f, "table {} has no seats", table),
+            ProblemError::TooFewSeats { n_guests, n_seats } => {
+                write!(f, "there are only {} seats for {} guests", n_seats, n_guests)
+            }
+            ProblemError::UnderCapacity { table, capacity } => write!(
+                f,
+                "table {} only seats {}, less than the minimum occupancy",
+                table, capacity
+            ),
+            ProblemError::TooFewGuests {
+                n_guests,
+                n_required,
+            } => write!(
+                f,
+                "{} guests are needed to fill every table to its minimum occupancy, but there are only {}",
+                n_required, n_guests
+            ),
+            ProblemError::UnknownGuest(guest) => write!(f, "there is no guest {}", guest),
+            ProblemError::UnitTooLarge { size, max_capacity } => write!(
+                f,
+                "{} guests must sit together, but the largest table only seats {}",
+                size, max_capacity
+            ),
+            ProblemError::Contradiction(i, j) => write!(
+                f,
+                "guests {} and {} must sit both together and apart",
+                i, j
+            ),
+            ProblemError::UnknownTable(table) => write!(f, "there is no table {}", table),
+            ProblemError::ConflictingPins(guest) => write!(
+                f,
+                "guest {} is pinned to a table they can't sit at",
+                guest
+            ),
+            ProblemError::Overbooked(table) => {
+                write!(f, "too many guests are pinned to table {}", table)
+            }
+            ProblemError::ShapeCount { n_shapes, n_tables } => write!(
+                f,
+                "there are {} table shapes for {} tables",
+                n_shapes, n_tables
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProblemError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProblemError::Relations(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RelationsError> for ProblemError {
+    fn from(e: RelationsError) -> Self {
+        ProblemError::Relations(e)
+    }
+}
+
+impl Problem {
+    pub fn new(relations: GuestRelations, n_tables: usize) -> Result<Self, ProblemError> {
+        Self::unchecked(relations, n_tables).checked()
+    }
+
+    /// Creates a problem where each table seats its own number of guests.
+    pub fn with_capacities(
+        relations: GuestRelations,
+        capacities: Vec<usize>,
+    ) -> Result<Self, ProblemError> {
+        let problem = Self {
+            capacities: Some(capacities.clone()),
+            ..Self::unchecked(relations, capacities.len())
+        };
+        problem.checked()
+    }
+
+    /// Requires every table to seat at least `min_occupancy` guests.
+    pub fn with_min_occupancy(self, min_occupancy: usize) -> Result<Self, ProblemError> {
+        let problem = Self {
+            min_occupancy,
+            ..self
+        };
+        problem.checked()
+    }
+
+    /// Requires guests to sit at, or away from, particular tables.
+    /// Both lists are of `(guest, table)` pairs.
+    pub fn with_pins(
+        self,
+        pins: Vec<(usize, usize)>,
+        forbidden: Vec<(usize, usize)>,
+    ) -> Result<Self, ProblemError> {
+        let problem = Self {
+            pins,
+            forbidden,
+            ..self
+        };
+        problem.checked()
+    }
+
+    // A problem with no extra requirements, which may not be valid.
+    fn unchecked(relations: GuestRelations, n_tables: usize) -> Self {
+        Self {
+            relations,
+            n_tables,
+            capacities: None,
+            min_occupancy: 0,
+            together: Vec::new(),
+            apart: Vec::new(),
+            pins: Vec::new(),
+            forbidden: Vec::new(),
+            layout: None,
+            require_friend: false,
+            seating: Seating::default(),
+            likes_anyone: Vec::new(),
+        }
+    }
+
+    // Validates the problem, and builds the lookups that depend on it.
+    fn checked(mut self) -> Result<Self, ProblemError> {
+        self.validate()?;
+        self.seating = Seating::new(&self);
+        let relations = &self.relations;
+        self.likes_anyone = (0..relations.len())
+            .map(|guest| (0..relations.len()).any(|other| relations.relationship(guest, other) > 0))
+            .collect();
+        Ok(self)
+    }
+
+    /// Requires everyone who likes somebody at the wedding to sit
+    /// with at least one person they like.
+    pub fn with_friend_required(self) -> Self {
+        Self {
+            require_friend: true,
+            ..self
+        }
+    }
+
+    /// Scores relationships by where guests sit at their table,
+    /// rather than counting everyone at the table equally.
+    pub fn with_layout(self, layout: Layout) -> Result<Self, ProblemError> {
+        let problem = Self {
+            layout: Some(layout),
+            ..self
+        };
+        problem.checked()
+    }
+
+    /// Requires each list of guests to sit at the same table,
+    /// and each pair of guests to sit at different tables.
+    pub fn with_constraints(
+        self,
+        together: Vec<Vec<usize>>,
+        apart: Vec<(usize, usize)>,
+    ) -> Result<Self, ProblemError> {
+        let problem = Self {
+            together,
+            apart,
+            ..self
+        };
+        problem.checked()
+    }
+
+    /// Checks that a plan can be made for this problem.
+    /// The relationships themselves are checked when they are constructed.
+    pub fn validate(&self) -> Result<(), ProblemError> {
+        let n_guests = self.relations.len();
+
+        if self.n_tables == 0 {
+            return Err(ProblemError::NoTables);
+        }
+
+        if let Some(capacities) = &self.capacities {
+            if capacities.len() != self.n_tables {
+                return Err(ProblemError::CapacityCount {
+                    n_capacities: capacities.len(),
+                    n_tables: self.n_tables,
+                });
+            }
+            if let Some(table) = capacities.iter().position(|c| *c == 0) {
+                return Err(ProblemError::NoSeats(table));
+            }
+        }
+
+        let n_seats = self.n_seats();
+        if n_seats < n_guests {
+            return Err(ProblemError::TooFewSeats { n_guests, n_seats });
+        }
+
+        for (table, capacity) in self.capacities().enumerate() {
+            if capacity < self.min_occupancy {
+                return Err(ProblemError::UnderCapacity { table, capacity });
+            }
+        }
+        let n_required = self.min_occupancy * self.n_tables;
+        if n_required > n_guests {
+            return Err(ProblemError::TooFewGuests {
+                n_guests,
+                n_required,
+            });
+        }
+
+        if let Some(layout) = &self.layout {
+            let n_shapes = layout.shapes.len();
+            if n_shapes != 1 && n_shapes != self.n_tables {
+                return Err(ProblemError::ShapeCount {
+                    n_shapes,
+                    n_tables: self.n_tables,
+                });
+            }
+        }
+
+        self.validate_constraints()
+    }
+
+    fn validate_constraints(&self) -> Result<(), ProblemError> {
+        let n_guests = self.relations.len();
+        let table_constraints = self.pins.iter().chain(&self.forbidden);
+        let mentioned = self
+            .together
+            .iter()
+            .flatten()
+            .chain(self.apart.iter().flat_map(|(i, j)| vec![i, j]))
+            .chain(table_constraints.clone().map(|(guest, _)| guest));
+        for &guest in mentioned {
+            if guest >= n_guests {
+                return Err(ProblemError::UnknownGuest(guest));
+            }
+        }
+        for &(_, table) in table_constraints {
+            if table >= self.n_tables {
+                return Err(ProblemError::UnknownTable(table));
+            }
+        }
+
+        let max_capacity = self.capacities().max().unwrap_or(0);
+        let unit_of = self.unit_ids();
+        let mut unit_sizes = vec![0; n_guests];
+        for &unit in &unit_of {
+            unit_sizes[unit] += 1;
+        }
+        if let Some(&size) = unit_sizes.iter().find(|size| **size > max_capacity) {
+            return Err(ProblemError::UnitTooLarge { size, max_capacity });
+        }
+
+        for &(i, j) in &self.apart {
+            if unit_of[i] == unit_of[j] {
+                return Err(ProblemError::Contradiction(i, j));
+            }
+        }
+
+        // Pinning one guest pins everyone they must sit with.
+        let mut unit_tables = vec![None; n_guests];
+        for &(guest, table) in &self.pins {
+            let unit_table = &mut unit_tables[unit_of[guest]];
+            if unit_table.is_some_and(|t| t != table) {
+                return Err(ProblemError::ConflictingPins(guest));
+            }
+            *unit_table = Some(table);
+        }
+        for &(guest, table) in &self.forbidden {
+            if unit_tables[unit_of[guest]] == Some(table) {
+                return Err(ProblemError::ConflictingPins(guest));
+            }
+        }
+        for &(i, j) in &self.apart {
+            if unit_tables[unit_of[i]].is_some()
+                && unit_tables[unit_of[i]] == unit_tables[unit_of[j]]
+            {
+                return Err(ProblemError::Contradiction(i, j));
+            }
+        }
+
+        let mut n_pinned = vec![0; self.n_tables];
+        for (unit, table) in unit_tables.iter().enumerate() {
+            if let Some(table) = table {
+                n_pinned[*table] += unit_sizes[unit];
+            }
+        }
+        for (table, capacity) in self.capacities().enumerate() {
+            if n_pinned[table] > capacity {
+                return Err(ProblemError::Overbooked(table));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of guests the table seats.
+    /// # Panics
+    /// Panics if the table doesn't exist.
+    pub fn capacity(&self, table: usize) -> usize {
+        match &self.capacities {
+            None => {
+                assert!(table < self.n_tables);
+                // Round up, so there is a seat for everyone.
+                self.relations.len().div_ceil(self.n_tables)
+            }
+            Some(capacities) => capacities[table],
+        }
+    }
+
+    /// Returns the capacity of every table, in order.
+    pub fn capacities(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.n_tables).map(move |table| self.capacity(table))
+    }
+
+    /// Returns the total number of seats at all tables.
+    pub fn n_seats(&self) -> usize {
+        self.capacities().sum()
+    }
+
+    /// Returns `true` if some seats will be left empty.
+    pub fn has_empty_seats(&self) -> bool {
+        self.n_seats() > self.relations.len()
+    }
+
+    /// Returns how much a relationship counts between guests in the two
+    /// seats of the table. This is always 1 if there is no layout.
+    pub fn weight(&self, table: usize, seat1: usize, seat2: usize) -> i64 {
+        match &self.layout {
+            None => 1,
+            Some(layout) => layout.weight(table, self.capacity(table), seat1, seat2),
+        }
+    }
+
+    /// How much each pair of guests like each other.
+    pub fn relations(&self) -> &GuestRelations {
+        &self.relations
+    }
+
+    /// The lists of guests who must sit at the same table.
+    pub fn together(&self) -> &[Vec<usize>] {
+        &self.together
+    }
+
+    /// The pairs of guests who must sit at different tables.
+    pub fn apart(&self) -> &[(usize, usize)] {
+        &self.apart
+    }
+
+    /// The guests who must sit at a particular table, as `(guest, table)`.
+    pub fn pins(&self) -> &[(usize, usize)] {
+        &self.pins
+    }
+
+    /// The guests who must not sit at a particular table, as `(guest, table)`.
+    pub fn forbidden(&self) -> &[(usize, usize)] {
+        &self.forbidden
+    }
+
+    /// Returns the table the guest is pinned to, if any.
+    pub fn pinned_table(&self, guest: usize) -> Option<usize> {
+        self.seating.pinned[guest]
+    }
+
+    /// Returns `true` if the guest may be seated at the table.
+    pub fn allows(&self, guest: usize, table: usize) -> bool {
+        self.pinned_table(guest)
+            .is_none_or(|pinned| pinned == table)
+            && !self.seating.forbidden[guest].contains(&table)
+    }
+
+    /// Returns `true` if the guest likes anybody at the wedding.
+    pub fn likes_anyone(&self, guest: usize) -> bool {
+        self.likes_anyone[guest]
+    }
+
+    /// Labels each table with the lowest-numbered table it is interchangeable
+    /// with: one with the same capacity and shape, that no guest is pinned
+    /// to or forbidden from.
+    pub fn table_classes(&self) -> Vec<usize> {
+        let is_special = |table: usize| {
+            self.pins.iter().any(|(_, t)| *t == table)
+                || self.forbidden.iter().any(|(_, t)| *t == table)
+        };
+        let shape = |table: usize| self.layout.as_ref().map(|layout| layout.shape(table));
+        (0..self.n_tables)
+            .map(|table| {
+                if is_special(table) {
+                    return table;
+                }
+                (0..table)
+                    .find(|other| {
+                        !is_special(*other)
+                            && self.capacity(*other) == self.capacity(table)
+                            && shape(*other) == shape(table)
+                    })
+                    .unwrap_or(table)
+            })
+            .collect()
+    }
+
+    /// Returns the groups of guests who must be seated at the same table.
+    /// Overlapping `together` lists are merged, and everyone else is a
+    /// group of their own.
+    pub fn units(&self) -> Vec<Vec<usize>> {
+        let mut units = vec![Vec::new(); self.relations.len()];
+        for (guest, unit) in self.unit_ids().into_iter().enumerate() {
+            units[unit].push(guest);
+        }
+        units.retain(|unit| !unit.is_empty());
+        units
+    }
+
+    // Labels each guest with the lowest-numbered guest they must sit with.
+    fn unit_ids(&self) -> Vec<usize> {
+        // A union-find, where each guest points towards the root of their unit.
+        let mut parents: Vec<usize> = (0..self.relations.len()).collect();
+        fn root(parents: &mut [usize], mut guest: usize) -> usize {
+            while parents[guest] != guest {
+                parents[guest] = parents[parents[guest]];
+                guest = parents[guest];
+            }
+            guest
+        }
+
+        for list in &self.together {
+            for pair in list.windows(2) {
+                let (a, b) = (root(&mut parents, pair[0]), root(&mut parents, pair[1]));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+
+        (0..parents.len())
+            .map(|guest| root(&mut parents, guest))
+            .collect()
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+// The serialised form of `Problem`, before anything has been validated.
+#[derive(Deserialize)]
+pub(crate) struct UncheckedProblem {
+    relations: UncheckedRelations,
+    n_tables: usize,
+    #[serde(default)]
+    capacities: Option<Vec<usize>>,
+    #[serde(default)]
+    min_occupancy: usize,
+    #[serde(default, alias = "groups")]
+    together: Vec<Vec<usize>>,
+    #[serde(default)]
+    apart: Vec<(usize, usize)>,
+    #[serde(default)]
+    pins: Vec<(usize, usize)>,
+    #[serde(default)]
+    forbidden: Vec<(usize, usize)>,
+    #[serde(default)]
+    layout: Option<Layout>,
+    #[serde(default)]
+    require_friend: bool,
+}
+
+impl TryFrom<UncheckedProblem> for Problem {
+    type Error = ProblemError;
+
+    fn try_from(unchecked: UncheckedProblem) -> Result<Self, Self::Error> {
+        let problem = Self {
+            relations: GuestRelations::try_from(unchecked.relations)?,
+            n_tables: unchecked.n_tables,
+            capacities: unchecked.capacities,
+            min_occupancy: unchecked.min_occupancy,
+            together: unchecked.together,
+            apart: unchecked.apart,
+            pins: unchecked.pins,
+            forbidden: unchecked.forbidden,
+            layout: unchecked.layout,
+            require_friend: unchecked.require_friend,
+            seating: Seating::default(),
+            likes_anyone: Vec::new(),
+        };
+        problem.checked()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(n_guests: usize) -> GuestRelations {
+        GuestRelations::new(vec![vec![0; n_guests]; n_guests]).unwrap()
+    }
+
+    #[test]
+    fn problem_validation() {
+        assert_eq!(Problem::new(pairs(4), 0), Err(ProblemError::NoTables));
+        assert_eq!(Problem::new(pairs(6), 3).unwrap().capacity(2), 2);
+
+        // Tables are made big enough for everyone, leaving a seat empty.
+        let uneven = Problem::new(pairs(5), 2).unwrap();
+        assert_eq!(uneven.capacity(0), 3);
+        assert!(uneven.has_empty_seats());
+    }
+
+    #[test]
+    fn problem_capacities() {
+        let problem = Problem::with_capacities(pairs(9), vec![2, 4, 3]).unwrap();
+        assert_eq!(problem.n_tables, 3);
+        assert_eq!(problem.capacities().collect::<Vec<_>>(), vec![2, 4, 3]);
+        assert!(!problem.has_empty_seats());
+
+        assert_eq!(
+            Problem::with_capacities(pairs(9), vec![2, 4, 2]),
+            Err(ProblemError::TooFewSeats {
+                n_guests: 9,
+                n_seats: 8
+            })
+        );
+        assert_eq!(
+            Problem::with_capacities(pairs(4), vec![4, 0]),
+            Err(ProblemError::NoSeats(1))
+        );
+
+        let wrong_count =
+            r#"{"relations":{"relationships":[[0,1],[1,0]]},"n_tables":2,"capacities":[2]}"#;
+        assert!(serde_json::from_str::<Problem>(wrong_count).is_err());
+    }
+
+    #[test]
+    fn problem_min_occupancy() {
+        let problem = Problem::with_capacities(pairs(7), vec![4, 4]).unwrap();
+        assert!(problem.clone().with_min_occupancy(3).is_ok());
+        assert_eq!(
+            problem.clone().with_min_occupancy(4),
+            Err(ProblemError::TooFewGuests {
+                n_guests: 7,
+                n_required: 8
+            })
+        );
+
+        let problem = Problem::with_capacities(pairs(7), vec![6, 2]).unwrap();
+        assert_eq!(
+            problem.with_min_occupancy(3),
+            Err(ProblemError::UnderCapacity {
+                table: 1,
+                capacity: 2
+            })
+        );
+    }
+
+    #[test]
+    fn problem_constraints() {
+        let problem = Problem::new(pairs(8), 2).unwrap();
+
+        let constrained = problem
+            .clone()
+            .with_constraints(vec![vec![0, 1], vec![5, 6], vec![1, 7]], vec![(0, 5)])
+            .unwrap();
+        let mut units = constrained.units();
+        units.sort();
+        assert_eq!(
+            units,
+            vec![vec![0, 1, 7], vec![2], vec![3], vec![4], vec![5, 6]]
+        );
+
+        assert_eq!(
+            problem
+                .clone()
+                .with_constraints(vec![vec![0, 1], vec![1, 2]], vec![(2, 0)]),
+            Err(ProblemError::Contradiction(2, 0))
+        );
+        assert_eq!(
+            problem
+                .clone()
+                .with_constraints(vec![vec![0, 1, 2], vec![2, 3, 4]], vec![]),
+            Err(ProblemError::UnitTooLarge {
+                size: 5,
+                max_capacity: 4
+            })
+        );
+        assert_eq!(
+            problem.with_constraints(vec![], vec![(3, 8)]),
+            Err(ProblemError::UnknownGuest(8))
+        );
+    }
+
+    #[test]
+    fn problem_pins() {
+        let problem = Problem::new(pairs(6), 2)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1]], vec![(0, 2)])
+            .unwrap();
+
+        let pinned = problem
+            .clone()
+            .with_pins(vec![(0, 1), (3, 1)], vec![(4, 1)])
+            .unwrap();
+        assert_eq!(pinned.pinned_table(0), Some(1));
+        assert_eq!(pinned.pinned_table(1), None);
+        assert!(!pinned.allows(0, 0));
+        assert!(!pinned.allows(4, 1));
+        assert!(pinned.allows(4, 0));
+
+        assert_eq!(
+            problem.clone().with_pins(vec![(0, 0), (1, 1)], vec![]),
+            Err(ProblemError::ConflictingPins(1))
+        );
+        assert_eq!(
+            problem.clone().with_pins(vec![(0, 0)], vec![(1, 0)]),
+            Err(ProblemError::ConflictingPins(1))
+        );
+        assert_eq!(
+            problem.clone().with_pins(vec![(1, 0), (2, 0)], vec![]),
+            Err(ProblemError::Contradiction(0, 2))
+        );
+        assert_eq!(
+            problem
+                .clone()
+                .with_pins(vec![(0, 1), (3, 1), (4, 1)], vec![]),
+            Err(ProblemError::Overbooked(1))
+        );
+        assert_eq!(
+            problem.with_pins(vec![(0, 2)], vec![]),
+            Err(ProblemError::UnknownTable(2))
+        );
+    }
+
+    #[test]
+    fn problem_deserialise_checked() {
+        let ok = r#"{"relations":{"relationships":[[0,1],[1,0]]},"n_tables":1}"#;
+        assert!(serde_json::from_str::<Problem>(ok).is_ok());
+
+        let no_tables = r#"{"relations":{"relationships":[[0,1],[1,0]]},"n_tables":0}"#;
+        let err = serde_json::from_str::<Problem>(no_tables).unwrap_err();
+        assert!(err.to_string().contains("there are no tables"));
+
+        let groups =
+            r#"{"relations":{"relationships":[[0,1],[1,0]]},"n_tables":1,"groups":[[0,1]]}"#;
+        let problem = serde_json::from_str::<Problem>(groups).unwrap();
+        assert_eq!(problem.together, vec![vec![0, 1]]);
+    }
+}
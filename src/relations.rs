@@ -0,0 +1,230 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A complete, undirected graph that models the relationship between
+/// all guests at a wedding.
+/// Guests are indexed as `usize`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "UncheckedRelations")]
+pub struct GuestRelations {
+    // A square array, symmetrical (ie `relationships[i][j] == relationships[j][i]`)
+    // with zeros along the diagonal.
+    relationships: Vec<Vec<i64>>,
+}
+
+/// How to resolve a pair of guests who disagree about their relationship,
+/// ie `relationships[i][j] != relationships[j][i]`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Symmetrize {
+    /// Both guests get the mean of the two values, rounded towards zero.
+    Mean,
+    /// Both guests get the larger of the two values.
+    Max,
+}
+
+/// The ways in which a matrix can fail to describe a valid `GuestRelations`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum RelationsError {
+    /// There are no guests at all.
+    Empty,
+    /// The given row does not have one entry per guest.
+    NonSquare {
+        row: usize,
+        len: usize,
+        expected: usize,
+    },
+    /// The two guests disagree about their relationship.
+    Asymmetric(usize, usize),
+    /// The guest has a relationship with themselves.
+    SelfLoop(usize),
+}
+
+impl fmt::Display for RelationsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelationsError::Empty => write!(f, "there are no guests"),
+            RelationsError::NonSquare { row, len, expected } => write!(
+                f,
+                "row {} has {} entries, but there are {} guests",
+                row, len, expected
+            ),
+            RelationsError::Asymmetric(i, j) => write!(
+                f,
+                "guests {} and {} disagree about their relationship",
+                i, j
+            ),
+            RelationsError::SelfLoop(i) => {
+                write!(f, "guest {} has a non-zero relationship with themselves", i)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RelationsError {}
+
+impl GuestRelations {
+    /// Checks that the matrix is nonempty, square, symmetrical and
+    /// has zeros along the diagonal.
+    pub fn new(relationships: Vec<Vec<i64>>) -> Result<Self, RelationsError> {
+        check_shape(&relationships)?;
+
+        for (i, row) in relationships.iter().enumerate() {
+            for j in 0..i {
+                if row[j] != relationships[j][i] {
+                    return Err(RelationsError::Asymmetric(j, i));
+                }
+            }
+        }
+
+        Ok(Self::new_unchecked(relationships))
+    }
+
+    /// Like `new`, but mismatched pairs are resolved with `mode`
+    /// instead of being rejected.
+    pub fn symmetrized(
+        mut relationships: Vec<Vec<i64>>,
+        mode: Symmetrize,
+    ) -> Result<Self, RelationsError> {
+        check_shape(&relationships)?;
+
+        let n_guests = relationships.len();
+        let pairs = (0..n_guests).flat_map(|i| (0..i).map(move |j| (i, j)));
+        for (i, j) in pairs {
+            let (a, b) = (relationships[i][j], relationships[j][i]);
+            let resolved = match mode {
+                // Widen, so the sum can't overflow. The mean always fits.
+                Symmetrize::Mean => ((i128::from(a) + i128::from(b)) / 2) as i64,
+                Symmetrize::Max => a.max(b),
+            };
+            relationships[i][j] = resolved;
+            relationships[j][i] = resolved;
+        }
+
+        Ok(Self::new_unchecked(relationships))
+    }
+
+    pub fn new_unchecked(relationships: Vec<Vec<i64>>) -> Self {
+        Self { relationships }
+    }
+
+    /// Returns the degree of friendship two guests have.
+    /// Positive is good, negative is bad. 0 is either unmet or self.
+    /// # Panics
+    /// Panics if either guest is unknown (out of bounds).
+    pub fn relationship(&self, guest1: usize, guest2: usize) -> i64 {
+        self.relationships[guest1][guest2]
+    }
+
+    /// Returns the number of guests.
+    pub fn len(&self) -> usize {
+        self.relationships.len()
+    }
+
+    /// Returns `true` if there are no guests.
+    pub fn is_empty(&self) -> bool {
+        self.relationships.is_empty()
+    }
+
+    /// Returns an iterator over the relationships. Should be combined with `.enumerate()`
+    /// if you want the indicies of the relevant guests.
+    pub fn iter(&self) -> impl Iterator<Item = impl Iterator<Item = i64> + '_> + '_ {
+        self.relationships.iter().map(|row| row.iter().copied())
+    }
+}
+
+// Checks everything except symmetry.
+fn check_shape(relationships: &[Vec<i64>]) -> Result<(), RelationsError> {
+    let n_guests = relationships.len();
+    if n_guests == 0 {
+        return Err(RelationsError::Empty);
+    }
+
+    for (i, row) in relationships.iter().enumerate() {
+        if row.len() != n_guests {
+            return Err(RelationsError::NonSquare {
+                row: i,
+                len: row.len(),
+                expected: n_guests,
+            });
+        }
+        if row[i] != 0 {
+            return Err(RelationsError::SelfLoop(i));
+        }
+    }
+
+    Ok(())
+}
+
+// The serialised form of `GuestRelations`, before it has been validated.
+#[derive(Deserialize)]
+pub(crate) struct UncheckedRelations {
+    relationships: Vec<Vec<i64>>,
+}
+
+impl TryFrom<UncheckedRelations> for GuestRelations {
+    type Error = RelationsError;
+
+    fn try_from(unchecked: UncheckedRelations) -> Result<Self, Self::Error> {
+        Self::new(unchecked.relationships)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relations_validation() {
+        assert_eq!(GuestRelations::new(vec![]), Err(RelationsError::Empty));
+        assert_eq!(
+            GuestRelations::new(vec![vec![0, 1], vec![1]]),
+            Err(RelationsError::NonSquare {
+                row: 1,
+                len: 1,
+                expected: 2
+            })
+        );
+        assert_eq!(
+            GuestRelations::new(vec![vec![0, 1], vec![2, 0]]),
+            Err(RelationsError::Asymmetric(0, 1))
+        );
+        assert_eq!(
+            GuestRelations::new(vec![vec![0, 1], vec![1, 3]]),
+            Err(RelationsError::SelfLoop(1))
+        );
+        assert!(GuestRelations::new(vec![vec![0, -4], vec![-4, 0]]).is_ok());
+    }
+
+    #[test]
+    fn relations_symmetrized() {
+        let matrix = vec![vec![0, 1, 5], vec![3, 0, -2], vec![5, 2, 0]];
+
+        let mean = GuestRelations::symmetrized(matrix.clone(), Symmetrize::Mean).unwrap();
+        assert_eq!(mean.relationship(0, 1), 2);
+        assert_eq!(mean.relationship(1, 0), 2);
+        assert_eq!(mean.relationship(1, 2), 0);
+        assert_eq!(mean.relationship(0, 2), 5);
+
+        let max = GuestRelations::symmetrized(matrix, Symmetrize::Max).unwrap();
+        assert_eq!(max.relationship(0, 1), 3);
+        assert_eq!(max.relationship(2, 1), 2);
+
+        let extreme = vec![vec![0, i64::MAX], vec![i64::MAX - 1, 0]];
+        let mean = GuestRelations::symmetrized(extreme, Symmetrize::Mean).unwrap();
+        assert_eq!(mean.relationship(0, 1), i64::MAX - 1);
+        let extreme = vec![vec![0, i64::MIN], vec![i64::MIN + 1, 0]];
+        let mean = GuestRelations::symmetrized(extreme, Symmetrize::Mean).unwrap();
+        assert_eq!(mean.relationship(0, 1), i64::MIN + 1);
+    }
+
+    #[test]
+    fn relations_deserialise_checked() {
+        let ok = r#"{"relationships":[[0,1],[1,0]]}"#;
+        assert!(serde_json::from_str::<GuestRelations>(ok).is_ok());
+
+        let asymmetric = r#"{"relationships":[[0,1],[0,0]]}"#;
+        assert!(serde_json::from_str::<GuestRelations>(asymmetric).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use crate::hill_climb::check_feasible;
+use crate::{Plan, PlanError, Problem, SeatingPlanner};
+
+use std::mem;
+
+/// How much memory `SubsetDpPlanner` may use, unless told otherwise.
+pub const DEFAULT_DP_MEMORY_LIMIT: usize = 1 << 30;
+
+// Marks a state that hasn't been solved yet, and one with no plan.
+const UNKNOWN: i64 = i64::MIN;
+const INFEASIBLE: i64 = i64::MIN + 1;
+
+/// Finds the plan with the most total happiness by dynamic programming over
+/// sets of guests. Since the tables are interchangeable, the table seating
+/// the lowest-numbered guest left can be chosen first, and the best way to
+/// seat everyone else only depends on who they are and how many tables
+/// are left.
+///
+/// The memory needed doubles with every guest, so problems needing more
+/// than the memory limit are refused, as are problems where the tables
+/// aren't interchangeable (different sizes, pins or forbidden tables) and
+/// problems with a layout.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct SubsetDpPlanner {
+    memory_limit: usize,
+}
+
+impl Default for SubsetDpPlanner {
+    fn default() -> Self {
+        Self {
+            memory_limit: DEFAULT_DP_MEMORY_LIMIT,
+        }
+    }
+}
+
+impl SubsetDpPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuses problems that would need more than this many bytes.
+    pub fn with_memory_limit(self, memory_limit: usize) -> Self {
+        Self { memory_limit }
+    }
+
+    /// Returns how many bytes solving the problem would take, or `None`
+    /// if it's too many to count.
+    pub fn memory_needed(problem: &Problem) -> Option<usize> {
+        let n_states = 1usize.checked_shl(problem.relations().len() as u32)?;
+        let width = TableCounts::new(problem).width();
+        n_states
+            .checked_mul(width)?
+            .checked_mul(mem::size_of::<i64>())
+    }
+}
+
+impl SeatingPlanner for SubsetDpPlanner {
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        if problem.layout.is_some() {
+            return Err(PlanError::Unsupported(
+                "subset DP doesn't know about seating layouts",
+            ));
+        }
+        if problem.table_classes().iter().any(|class| *class != 0) {
+            return Err(PlanError::Unsupported(
+                "subset DP needs every table to be interchangeable",
+            ));
+        }
+        if Self::memory_needed(problem).is_none_or(|needed| needed > self.memory_limit) {
+            return Err(PlanError::Unsupported(
+                "subset DP would need too much memory",
+            ));
+        }
+
+        let mut dp = Dp::new(problem);
+        let everyone = dp.full_set();
+        if dp.best(everyone, 0) == INFEASIBLE {
+            return Err(PlanError::Unpackable);
+        }
+        check_feasible(dp.plan(), problem)
+    }
+}
+
+// Which numbers of tables could have been filled after seating a given
+// number of guests.
+#[derive(Debug, Copy, Clone)]
+struct TableCounts {
+    n_guests: usize,
+    n_tables: usize,
+    capacity: usize,
+    min_occupancy: usize,
+}
+
+impl TableCounts {
+    fn new(problem: &Problem) -> Self {
+        Self {
+            n_guests: problem.relations().len(),
+            n_tables: problem.n_tables,
+            capacity: problem.capacity(0),
+            min_occupancy: problem.min_occupancy,
+        }
+    }
+
+    // The fewest and most tables the seated guests could fill, leaving
+    // enough tables for everyone else. Every filled table has someone at it.
+    fn range(&self, n_seated: usize) -> (usize, usize) {
+        let n_left = self.n_guests - n_seated;
+        let mut fewest = n_seated.div_ceil(self.capacity);
+        // Every table left must reach its minimum occupancy.
+        if let Some(most_left) = n_left.checked_div(self.min_occupancy) {
+            fewest = fewest.max(self.n_tables.saturating_sub(most_left));
+        }
+        let most = (n_seated / self.min_occupancy.max(1))
+            .min(self.n_tables.saturating_sub(n_left.div_ceil(self.capacity)));
+        (fewest, most)
+    }
+
+    // The most different numbers of tables any number of seated guests
+    // could have filled.
+    fn width(&self) -> usize {
+        (0..=self.n_guests)
+            .map(|n_seated| {
+                let (fewest, most) = self.range(n_seated);
+                (most + 1).saturating_sub(fewest)
+            })
+            .max()
+            .unwrap_or(0)
+            .max(1)
+    }
+}
+
+struct Dp<'a> {
+    problem: &'a Problem,
+    counts: TableCounts,
+    width: usize,
+    // The groups who must sit together, as sets of guests, in order of their
+    // lowest-numbered guest.
+    units: Vec<usize>,
+    // Index by guest to get the unit they're in.
+    unit_of: Vec<usize>,
+    // Index by unit, then by guest, to get the sum of the relationships
+    // from the unit to the guest.
+    towards: Vec<Vec<i64>>,
+    // Index by unit to get the happiness among the group.
+    within: Vec<i64>,
+    // Index by unit to get everyone someone in the unit must be kept apart from.
+    apart: Vec<usize>,
+    // Index by guest to get everyone they have a positive relationship with,
+    // if they need to sit with one of them.
+    friends: Vec<usize>,
+    // Index by set of guests left, then by tables filled, to get the most
+    // happiness the guests left can have.
+    memo: Vec<i64>,
+}
+
+impl<'a> Dp<'a> {
+    fn new(problem: &'a Problem) -> Self {
+        let relations = problem.relations();
+        let n_guests = relations.len();
+        let counts = TableCounts::new(problem);
+        let width = counts.width();
+
+        let mut units: Vec<Vec<usize>> = problem.units();
+        units.sort_by_key(|unit| unit.iter().min().copied());
+        let mut unit_of = vec![0; n_guests];
+        for (id, unit) in units.iter().enumerate() {
+            for guest in unit {
+                unit_of[*guest] = id;
+            }
+        }
+        let towards: Vec<Vec<i64>> = units
+            .iter()
+            .map(|unit| {
+                (0..n_guests)
+                    .map(|other| unit.iter().map(|g| relations.relationship(*g, other)).sum())
+                    .collect()
+            })
+            .collect();
+        let within = units
+            .iter()
+            .zip(&towards)
+            .map(|(unit, towards)| unit.iter().map(|g| towards[*g]).sum())
+            .collect();
+
+        let mut apart = vec![0; units.len()];
+        for &(i, j) in problem.apart() {
+            apart[unit_of[i]] |= 1 << j;
+            apart[unit_of[j]] |= 1 << i;
+        }
+
+        let friends = (0..n_guests)
+            .map(|guest| {
+                if !problem.require_friend {
+                    return 0;
+                }
+                (0..n_guests)
+                    .filter(|other| relations.relationship(guest, *other) > 0)
+                    .fold(0, |set, other| set | 1 << other)
+            })
+            .collect();
+
+        Self {
+            problem,
+            counts,
+            width,
+            units: units.iter().map(|unit| to_set(unit)).collect(),
+            unit_of,
+            towards,
+            within,
+            apart,
+            friends,
+            memo: vec![UNKNOWN; (1 << n_guests) * width],
+        }
+    }
+
+    fn full_set(&self) -> usize {
+        (1 << self.problem.relations().len()) - 1
+    }
+
+    // Where the state is kept in the memo, or `None` if there is no way
+    // to have filled that many tables.
+    fn index(&self, left: usize, n_filled: usize) -> Option<usize> {
+        let n_seated = self.problem.relations().len() - left.count_ones() as usize;
+        let (fewest, most) = self.counts.range(n_seated);
+        if n_filled < fewest || n_filled > most {
+            return None;
+        }
+        Some(left * self.width + n_filled - fewest)
+    }
+
+    // The most happiness the guests left can have at the tables that
+    // haven't been filled, or `INFEASIBLE`.
+    fn best(&mut self, left: usize, n_filled: usize) -> i64 {
+        let index = match self.index(left, n_filled) {
+            Some(index) => index,
+            None => return INFEASIBLE,
+        };
+        if self.memo[index] != UNKNOWN {
+            return self.memo[index];
+        }
+
+        let best = if left == 0 {
+            0
+        } else {
+            let mut best = INFEASIBLE;
+            for (table, happiness) in self.tables(left) {
+                let rest = self.best(left & !table, n_filled + 1);
+                if rest != INFEASIBLE {
+                    best = best.max(happiness + rest);
+                }
+            }
+            best
+        };
+        self.memo[index] = best;
+        best
+    }
+
+    // Every table that could seat the lowest-numbered guest left, along
+    // with how much happiness there would be at it.
+    fn tables(&self, left: usize) -> Vec<(usize, i64)> {
+        let first = self.unit_of[left.trailing_zeros() as usize];
+        let candidates: Vec<usize> = (first + 1..self.units.len())
+            .filter(|unit| self.units[*unit] & left != 0)
+            .collect();
+
+        let mut tables = Vec::new();
+        let table = Table {
+            guests: self.units[first],
+            happiness: self.within[first],
+            apart: self.apart[first],
+        };
+        self.extend_table(table, &candidates, &mut tables);
+        tables
+    }
+
+    // Adds every valid table made of `table` and some of the candidates.
+    fn extend_table(&self, table: Table, candidates: &[usize], tables: &mut Vec<(usize, i64)>) {
+        let size = table.guests.count_ones() as usize;
+        if size >= self.counts.min_occupancy.max(1) && self.everyone_has_a_friend(table.guests) {
+            tables.push((table.guests, table.happiness));
+        }
+
+        for (i, unit) in candidates.iter().enumerate() {
+            let members = self.units[*unit];
+            let too_big = size + members.count_ones() as usize > self.counts.capacity;
+            if too_big || table.apart & members != 0 {
+                continue;
+            }
+
+            let gain: i64 = guests(table.guests)
+                .map(|guest| self.towards[*unit][guest])
+                .sum();
+            let bigger = Table {
+                guests: table.guests | members,
+                happiness: table.happiness + 2 * gain + self.within[*unit],
+                apart: table.apart | self.apart[*unit],
+            };
+            self.extend_table(bigger, &candidates[i + 1..], tables);
+        }
+    }
+
+    fn everyone_has_a_friend(&self, table: usize) -> bool {
+        guests(table).all(|guest| self.friends[guest] == 0 || self.friends[guest] & table != 0)
+    }
+
+    // Retraces the choices that led to the best total happiness.
+    fn plan(&mut self) -> Plan {
+        let mut plan = vec![Vec::new(); self.problem.n_tables];
+        let mut left = self.full_set();
+        let mut n_filled = 0;
+        while left != 0 {
+            let target = self.best(left, n_filled);
+            let (table, _) = self
+                .tables(left)
+                .into_iter()
+                .find(|(table, happiness)| {
+                    let rest = self.best(left & !table, n_filled + 1);
+                    rest != INFEASIBLE && happiness + rest == target
+                })
+                .expect("The best plan was made from one of these tables.");
+            plan[n_filled] = guests(table).collect();
+            left &= !table;
+            n_filled += 1;
+        }
+        plan
+    }
+}
+
+// A table that is being built.
+#[derive(Debug, Copy, Clone)]
+struct Table {
+    guests: usize,
+    happiness: i64,
+    // Everyone someone at the table must be kept apart from.
+    apart: usize,
+}
+
+fn to_set(guests: &[usize]) -> usize {
+    guests.iter().fold(0, |set, guest| set | 1 << guest)
+}
+
+// The guests in the set, in order.
+fn guests(mut set: usize) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if set == 0 {
+            return None;
+        }
+        let guest = set.trailing_zeros() as usize;
+        set &= set - 1;
+        Some(guest)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+
+    use crate::metrics::Metrics;
+    use crate::test_util::{assert_feasible, random_wedding};
+    use crate::BruteForcePlanner;
+
+    #[test]
+    fn subset_dp_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (n_guests, n_tables) in [(6, 2), (8, 3), (10, 3), (11, 4), (12, 3)] {
+            let problem = random_wedding(&mut rng, n_guests, n_tables);
+            let plan = SubsetDpPlanner::new().plan(&problem).unwrap();
+            let optimum = BruteForcePlanner::new().plan(&problem).unwrap();
+            assert_eq!(
+                Metrics::new(&plan, &problem).total_happiness(),
+                Metrics::new(&optimum, &problem).total_happiness()
+            );
+        }
+    }
+
+    #[test]
+    fn subset_dp_respects_constraints() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let problem = random_wedding(&mut rng, 11, 4)
+            .with_min_occupancy(2)
+            .unwrap()
+            .with_constraints(vec![vec![0, 1, 2]], vec![(3, 4), (4, 5)])
+            .unwrap()
+            .with_friend_required();
+        let plan = SubsetDpPlanner::new().plan(&problem).unwrap();
+        let optimum = BruteForcePlanner::new().plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+        assert_eq!(
+            Metrics::new(&plan, &problem).total_happiness(),
+            Metrics::new(&optimum, &problem).total_happiness()
+        );
+    }
+
+    #[test]
+    fn subset_dp_refuses_big_problems() {
+        let problem = random_wedding(&mut StdRng::seed_from_u64(2), 20, 4);
+        let mut planner = SubsetDpPlanner::new().with_memory_limit(1 << 20);
+        assert!(matches!(
+            planner.plan(&problem),
+            Err(PlanError::Unsupported(_))
+        ));
+
+        let pinned = random_wedding(&mut StdRng::seed_from_u64(3), 8, 2)
+            .with_pins(vec![(0, 1)], vec![])
+            .unwrap();
+        assert!(matches!(
+            SubsetDpPlanner::new().plan(&pinned),
+            Err(PlanError::Unsupported(_))
+        ));
+    }
+}
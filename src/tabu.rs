@@ -0,0 +1,200 @@
+use crate::hill_climb::{
+    check_feasible, check_groups, moved_guests, random_plan, MoveScorer, Neighbourhood,
+};
+use crate::objective::{Objective, TotalHappiness};
+use crate::{GuestSampling, LocalSearch, MoveMix, Plan, PlanError, Problem, SeatingPlanner};
+
+use std::num::NonZeroUsize;
+
+use rand::prelude::*;
+
+// How many iterations a moved guest stays tabu, unless told otherwise.
+const DEFAULT_TENURE: usize = 10;
+
+// How many iterations without finding a better plan before we give up,
+// unless told otherwise.
+const DEFAULT_PATIENCE: usize = 100;
+
+/// Tabu search: always makes the best move available, even if it makes
+/// things worse, but doesn't move anyone who was moved recently unless
+/// that leads to the best plan seen so far.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct TabuSearchPlanner<R, O = TotalHappiness> {
+    rng: R,
+    objective: O,
+    // How many iterations a guest who was moved can't be moved again.
+    tenure: usize,
+    // How many random moves to look at each iteration.
+    // If missing, we look at every move.
+    sample_size: Option<NonZeroUsize>,
+    // How many iterations without a new best plan before we stop.
+    patience: usize,
+    moves: MoveMix,
+    sampling: GuestSampling,
+}
+
+impl<R> TabuSearchPlanner<R>
+where
+    R: Rng,
+{
+    pub fn new(rng: R) -> Self {
+        Self::with_objective(rng, TotalHappiness)
+    }
+}
+
+impl<R, O> TabuSearchPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    pub fn with_objective(rng: R, objective: O) -> Self {
+        Self {
+            rng,
+            objective,
+            tenure: DEFAULT_TENURE,
+            sample_size: None,
+            patience: DEFAULT_PATIENCE,
+            moves: MoveMix::default(),
+            sampling: GuestSampling::default(),
+        }
+    }
+
+    /// Sets how many iterations a guest stays in place after being moved.
+    pub fn with_tenure(self, tenure: usize) -> Self {
+        Self { tenure, ..self }
+    }
+
+    /// Looks at this many random moves each iteration, rather than every
+    /// move, which is much quicker for big weddings.
+    pub fn with_sample_size(self, sample_size: NonZeroUsize) -> Self {
+        Self {
+            sample_size: Some(sample_size),
+            ..self
+        }
+    }
+
+    /// Sets how many iterations without finding a better plan we allow
+    /// before stopping.
+    pub fn with_patience(self, patience: usize) -> Self {
+        Self { patience, ..self }
+    }
+
+    /// Chooses which kinds of random move are sampled. Only used with
+    /// a sample size, as otherwise we look at every swap and relocation.
+    pub fn with_moves(self, moves: MoveMix) -> Self {
+        Self { moves, ..self }
+    }
+
+    /// Chooses how the guests to move are picked. Only used with a sample
+    /// size, like `with_moves`.
+    pub fn with_sampling(self, sampling: GuestSampling) -> Self {
+        Self { sampling, ..self }
+    }
+}
+
+impl<R, O> SeatingPlanner for TabuSearchPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn plan(&mut self, problem: &Problem) -> Result<Plan, PlanError> {
+        let plan = random_plan(&mut self.rng, problem)?;
+        self.improve(problem, plan)
+    }
+}
+
+impl<R, O> LocalSearch for TabuSearchPlanner<R, O>
+where
+    R: Rng,
+    O: Objective,
+{
+    fn improve(&mut self, problem: &Problem, mut plan: Plan) -> Result<Plan, PlanError> {
+        check_groups(&plan, problem)?;
+        let neighbourhood = Neighbourhood::new(problem)
+            .with_moves(self.moves)
+            .with_sampling(self.sampling);
+
+        let mut scorer = MoveScorer::new(&neighbourhood, &self.objective, &plan);
+        let mut best = plan.clone();
+        let mut best_fitness = scorer.fitness(&plan);
+
+        // Index by guest to get the first iteration they may move again.
+        let mut tabu_until = vec![0; problem.relations().len()];
+
+        let mut n_stale = 0;
+        let mut iteration = 0;
+        while n_stale < self.patience {
+            iteration += 1;
+            n_stale += 1;
+
+            let rng = &mut self.rng;
+            let moves = match self.sample_size {
+                Some(n) => (0..n.get())
+                    .filter_map(|_| neighbourhood.random_move(&mut *rng, &plan))
+                    .collect(),
+                None => neighbourhood.all_moves(rng, &plan),
+            };
+
+            let mut chosen = None;
+            for change in moves {
+                let is_tabu = moved_guests(&plan, &change)
+                    .iter()
+                    .any(|(guest, _)| tabu_until[*guest] > iteration);
+
+                let new_fitness = scorer.propose(&mut plan, &change);
+                scorer.reject(&mut plan, &change);
+
+                // Aspiration: a tabu move is fine if it beats everything so far.
+                let admissible = !is_tabu || new_fitness > best_fitness;
+                let is_better = chosen
+                    .as_ref()
+                    .is_none_or(|(chosen_fitness, _)| new_fitness > *chosen_fitness);
+                if admissible && is_better {
+                    chosen = Some((new_fitness, change));
+                }
+            }
+
+            // If every move is tabu, wait for some to expire.
+            if let Some((new_fitness, change)) = chosen {
+                for (guest, _) in moved_guests(&plan, &change) {
+                    tabu_until[guest] = iteration + self.tenure + 1;
+                }
+                scorer.propose(&mut plan, &change);
+                scorer.keep(&mut plan, &change);
+
+                if new_fitness > best_fitness {
+                    best_fitness = new_fitness;
+                    best.clone_from(&plan);
+                    n_stale = 0;
+                }
+            }
+        }
+
+        check_feasible(best, problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{assert_feasible, assert_improves_on_start, constrained_wedding};
+
+    #[test]
+    fn tabu_never_worse_than_start() {
+        for seed in 0..3 {
+            assert_improves_on_start(seed, 16, 4, |rng| {
+                TabuSearchPlanner::new(rng).with_patience(20)
+            });
+        }
+    }
+
+    #[test]
+    fn sampled_tabu_respects_constraints() {
+        let problem = constrained_wedding(0);
+        let mut planner = TabuSearchPlanner::new(StdRng::seed_from_u64(0))
+            .with_sample_size(NonZeroUsize::new(20).unwrap());
+        let plan = planner.plan(&problem).unwrap();
+        assert_feasible(&plan, &problem);
+    }
+}
@@ -0,0 +1,81 @@
+// Weddings and checks shared by the planners' tests.
+
+use rand::prelude::*;
+
+use crate::hill_climb::random_plan;
+use crate::metrics::Metrics;
+use crate::{GuestRelations, Plan, Problem, SeatingPlanner, Symmetrize};
+
+// A wedding where it matters a lot who sits with whom.
+pub(crate) fn random_wedding(rng: &mut impl Rng, n_guests: usize, n_tables: usize) -> Problem {
+    let matrix = (0..n_guests)
+        .map(|i| {
+            (0..n_guests)
+                .map(|j| if i == j { 0 } else { rng.gen_range(-10..=10) })
+                .collect()
+        })
+        .collect();
+    let relations = GuestRelations::symmetrized(matrix, Symmetrize::Max).unwrap();
+    Problem::new(relations, n_tables).unwrap()
+}
+
+// A random wedding of 14 guests at tables for 6, 5 and 5. Guests 0, 1
+// and 2 must sit together, guest 4 apart from 3 and 5, guest 6 at
+// table 2, and guest 7 anywhere else.
+pub(crate) fn constrained_wedding(seed: u64) -> Problem {
+    let relations = random_wedding(&mut StdRng::seed_from_u64(seed), 14, 3)
+        .relations()
+        .clone();
+    Problem::with_capacities(relations, vec![6, 5, 5])
+        .unwrap()
+        .with_constraints(vec![vec![0, 1, 2]], vec![(3, 4), (4, 5)])
+        .unwrap()
+        .with_pins(vec![(6, 2)], vec![(7, 2)])
+        .unwrap()
+}
+
+// Checks that everyone is seated exactly once.
+pub(crate) fn assert_partition(plan: &Plan, n_guests: usize) {
+    let mut guests: Vec<usize> = plan.iter().flatten().copied().collect();
+    guests.sort_unstable();
+    assert_eq!(guests, (0..n_guests).collect::<Vec<_>>());
+}
+
+// Checks that a plan seats everyone, fits the tables, and keeps every
+// hard constraint.
+pub(crate) fn assert_feasible(plan: &Plan, problem: &Problem) {
+    assert_partition(plan, problem.relations().len());
+    for (table, guests) in plan.iter().enumerate() {
+        assert!(guests.len() >= problem.min_occupancy);
+        assert!(guests.len() <= problem.capacity(table));
+    }
+    assert_eq!(Metrics::new(plan, problem).n_violations(), 0);
+}
+
+// Checks that a planner ends up happier than the random plan it starts
+// from, on a random wedding. The planner is built from an rng seeded the
+// same way as the one used to draw its start.
+pub(crate) fn assert_improves_on_start<P>(
+    seed: u64,
+    n_guests: usize,
+    n_tables: usize,
+    planner: impl FnOnce(StdRng) -> P,
+) where
+    P: SeatingPlanner,
+{
+    let problem = random_wedding(&mut StdRng::seed_from_u64(seed), n_guests, n_tables);
+    let rng = StdRng::seed_from_u64(seed);
+    let start = random_plan(rng.clone(), &problem).unwrap();
+    let plan = planner(rng).plan(&problem).unwrap();
+
+    assert_partition(&plan, n_guests);
+    let start_total = Metrics::new(&start, &problem).total_happiness();
+    let total = Metrics::new(&plan, &problem).total_happiness();
+    assert!(
+        total > start_total,
+        "seed {} went from {} to {}",
+        seed,
+        start_total,
+        total
+    );
+}
@@ -0,0 +1,188 @@
+//! Checks every planner against `BruteForcePlanner` on problems small
+//! enough to solve exactly.
+
+use dissertation::metrics::Metrics;
+use dissertation::{
+    BranchAndBoundPlanner, BruteForcePlanner, ClimbMode, GeneticPlanner, GreedyPlanner,
+    GuestRelations, HillClimbingPlanner, Insertion, KernighanLinPlanner, LahcPlanner, LnsPlanner,
+    MultilevelPlanner, Plan, Problem, Repair, SeatingPlanner, SimulatedAnnealingPlanner,
+    SubsetDpPlanner, Symmetrize, TabuSearchPlanner, MAX_BRUTE_FORCE_GUESTS,
+};
+
+use rand::prelude::*;
+
+type Float = ordered_float::NotNan<f64>;
+
+// How many problems each planner is checked on.
+const N_PROBLEMS: u64 = 20;
+
+struct Case {
+    name: &'static str,
+    planner: Box<dyn SeatingPlanner>,
+    // Whether the planner must always find the optimum.
+    exact: bool,
+    // The largest total gap to the optimum over all the problems, as a
+    // fraction of the sum of the optima.
+    max_gap: f64,
+}
+
+fn cases(seed: u64) -> Vec<Case> {
+    let rng = StdRng::seed_from_u64(seed);
+    let threshold = Float::new(0.01).unwrap();
+    vec![
+        Case {
+            name: "branch and bound",
+            planner: Box::new(BranchAndBoundPlanner::new()),
+            exact: true,
+            max_gap: 0.0,
+        },
+        Case {
+            name: "subset DP",
+            planner: Box::new(SubsetDpPlanner::new()),
+            exact: true,
+            max_gap: 0.0,
+        },
+        Case {
+            name: "hill climbing",
+            planner: Box::new(HillClimbingPlanner::new(rng.clone(), threshold)),
+            exact: false,
+            max_gap: 0.1,
+        },
+        Case {
+            name: "steepest ascent",
+            planner: Box::new(
+                HillClimbingPlanner::new(rng.clone(), threshold)
+                    .with_mode(ClimbMode::SteepestAscent),
+            ),
+            exact: false,
+            max_gap: 0.1,
+        },
+        Case {
+            name: "late acceptance",
+            planner: Box::new(LahcPlanner::new(rng.clone(), threshold)),
+            exact: false,
+            max_gap: 0.05,
+        },
+        Case {
+            name: "simulated annealing",
+            planner: Box::new(SimulatedAnnealingPlanner::new(rng.clone())),
+            exact: false,
+            max_gap: 0.05,
+        },
+        Case {
+            name: "tabu search",
+            planner: Box::new(TabuSearchPlanner::new(rng.clone())),
+            exact: false,
+            max_gap: 0.05,
+        },
+        Case {
+            name: "genetic",
+            planner: Box::new(GeneticPlanner::new(rng.clone())),
+            exact: false,
+            max_gap: 0.05,
+        },
+        Case {
+            name: "LNS",
+            planner: Box::new(LnsPlanner::new(rng.clone()).with_repair(Repair::Exact)),
+            exact: false,
+            max_gap: 0.05,
+        },
+        Case {
+            name: "multilevel",
+            planner: Box::new(MultilevelPlanner::new(rng.clone(), threshold)),
+            exact: false,
+            max_gap: 0.1,
+        },
+        Case {
+            name: "Kernighan-Lin",
+            planner: Box::new(KernighanLinPlanner::new(rng)),
+            exact: false,
+            max_gap: 0.1,
+        },
+        Case {
+            name: "greedy",
+            planner: Box::new(GreedyPlanner::new(Insertion::Regret)),
+            exact: false,
+            max_gap: 0.2,
+        },
+    ]
+}
+
+// Up to 12 guests who mostly like each other, so the optima are positive
+// and the gaps are easy to compare.
+fn tiny_problem(rng: &mut impl Rng) -> Problem {
+    let n_tables = rng.gen_range(2..=3);
+    let n_guests = rng.gen_range(3 * n_tables..=MAX_BRUTE_FORCE_GUESTS.min(4 * n_tables));
+    let matrix = (0..n_guests)
+        .map(|i| {
+            (0..n_guests)
+                .map(|j| if i == j { 0 } else { rng.gen_range(-5..=10) })
+                .collect()
+        })
+        .collect();
+    let relations = GuestRelations::symmetrized(matrix, Symmetrize::Max).unwrap();
+    Problem::new(relations, n_tables).unwrap()
+}
+
+fn assert_valid(name: &str, plan: &Plan, problem: &Problem) {
+    let mut guests: Vec<usize> = plan.iter().flatten().copied().collect();
+    guests.sort_unstable();
+    assert_eq!(
+        guests,
+        (0..problem.relations().len()).collect::<Vec<_>>(),
+        "{} doesn't seat everyone exactly once",
+        name
+    );
+    assert_eq!(
+        plan.len(),
+        problem.n_tables,
+        "{} has the wrong tables",
+        name
+    );
+    for (table, guests) in plan.iter().enumerate() {
+        assert!(
+            guests.len() <= problem.capacity(table),
+            "{} overfills table {}",
+            name,
+            table
+        );
+    }
+    assert_eq!(Metrics::new(plan, problem).n_violations(), 0);
+}
+
+#[test]
+fn planners_match_brute_force() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let problems: Vec<Problem> = (0..N_PROBLEMS).map(|_| tiny_problem(&mut rng)).collect();
+    let optima: Vec<i64> = problems
+        .iter()
+        .map(|problem| {
+            let plan = BruteForcePlanner::new().plan(problem).unwrap();
+            assert_valid("brute force", &plan, problem);
+            Metrics::new(&plan, problem).total_happiness()
+        })
+        .collect();
+    let total_optimum: i64 = optima.iter().sum();
+
+    for case in &mut cases(0) {
+        let mut total_gap = 0;
+        for (problem, optimum) in problems.iter().zip(&optima) {
+            let plan = case.planner.plan(problem).unwrap();
+            assert_valid(case.name, &plan, problem);
+
+            let gap = optimum - Metrics::new(&plan, problem).total_happiness();
+            assert!(gap >= 0, "{} beat the optimum", case.name);
+            assert!(!case.exact || gap == 0, "{} missed the optimum", case.name);
+            total_gap += gap;
+        }
+
+        let relative_gap = total_gap as f64 / total_optimum as f64;
+        println!("{:<20} gap {:.3}", case.name, relative_gap);
+        assert!(
+            relative_gap <= case.max_gap,
+            "{} is {:.3} from optimal",
+            case.name,
+            relative_gap
+        );
+    }
+}